@@ -0,0 +1,48 @@
+use std::{env, fs, path::Path};
+
+/// The shaderc release pinned in Cargo.toml, which names the compiler in the SPIR-V cache.
+const SHADERC_VERSION: &str = "0.6.2";
+
+fn shaderc_version() -> String {
+    let manifest = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("Cargo.toml");
+    println!("cargo:rerun-if-changed={}", manifest.display());
+    let pin = format!("shaderc = {{ version = \"={}\"", SHADERC_VERSION);
+    if !fs::read_to_string(&manifest).unwrap_or_default().contains(&pin) {
+        panic!("bump SHADERC_VERSION in build.rs along with the shaderc pin in Cargo.toml!");
+    }
+    let mut version = format!("shaderc {}", SHADERC_VERSION);
+    // a prebuilt library replaces the one the crate would build
+    println!("cargo:rerun-if-env-changed=SHADERC_LIB_DIR");
+    if let Ok(dir) = env::var("SHADERC_LIB_DIR") {
+        version += &format!(", from {}", dir);
+    }
+    version
+}
+
+// Embeds the SPIR-V cache entries in `shaders/precompiled` for `shader::cache`.
+fn main() {
+    println!("cargo:rustc-env=UG_SHADERC_VERSION={}", shaderc_version());
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("precompiled.rs");
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("shaders").join("precompiled");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut table = String::from("&[\n");
+    if env::var_os("CARGO_FEATURE_PRECOMPILED_SHADERS").is_some() {
+        let mut entries: Vec<_> = fs::read_dir(&dir)
+            .expect("precompiled-shaders needs shaders/precompiled, run with --precompile-shaders first!")
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == "spv"))
+            .collect();
+        entries.sort();
+        for spv in entries {
+            let key = spv.file_stem().unwrap().to_str().unwrap();
+            let deps = spv.with_extension("deps");
+            println!("cargo:rerun-if-changed={}", spv.display());
+            println!("cargo:rerun-if-changed={}", deps.display());
+            table += &format!("    ({:?}, include_bytes!({:?}), include_str!({:?})),\n", key, spv, deps);
+        }
+    }
+    table += "]\n";
+    fs::write(out, table).unwrap();
+}
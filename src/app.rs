@@ -1,9 +1,40 @@
 use std::time::{Duration, Instant};
+use crate::{capture, math, time};
 use winit::{
     event::{self, Event, WindowEvent, DeviceEvent, MouseScrollDelta},
     event_loop::{ControlFlow, EventLoop},
 };
 
+/// When frames are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Redraw {
+    /// Every frame the present mode allows.
+    Continuous,
+    /// After input and resizes, and while `App::needs_redraw` says so.
+    OnDemand,
+}
+
+/// How `run` presents and paces frames.
+#[derive(Debug, Clone, Copy)]
+pub struct RunConfig {
+    /// Defaults to `Fifo`, the one mode every surface supports. wgpu can't list the modes a
+    /// surface supports, so an unsupported `Mailbox` or `Immediate` becomes `Fifo` inside wgpu.
+    pub present_mode: wgpu::PresentMode,
+    pub redraw: Redraw,
+    /// Frame rate to stay under, `None` leaves the pacing to the present mode.
+    pub max_fps: Option<f32>,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            present_mode: wgpu::PresentMode::Fifo,
+            redraw: Redraw::Continuous,
+            max_fps: None,
+        }
+    }
+}
+
 pub trait App: 'static + Sized {
     fn optional_features() -> wgpu::Features {
         wgpu::Features::empty()
@@ -15,20 +46,158 @@ pub trait App: 'static + Sized {
         wgpu::Limits::default()
     }
 
+    fn run_config() -> RunConfig {
+        RunConfig::default()
+    }
+
+    /// Asked after the events of each loop iteration, e.g. true while animating or after
+    /// polling files that changed. Only decides anything with `Redraw::OnDemand`.
+    fn needs_redraw(&mut self) -> bool {
+        false
+    }
+
+    /// When to ask `needs_redraw` again while idle, without waiting for an event.
+    fn next_check(&self) -> Option<Instant> {
+        None
+    }
+
     #[allow(unused_variables)]
     fn mouse_move(&mut self, x: f32, y: f32) {}
 
     #[allow(unused_variables)]
     fn mouse_wheel(&mut self, delta: f32) {}
 
+    /// Left button released close to where it was pressed, in physical pixels.
+    #[allow(unused_variables)]
+    fn click(&mut self, x: f32, y: f32) {}
+
     fn init(device: &wgpu::Device, queue: &wgpu::Queue, sc_desc: &wgpu::SwapChainDescriptor) -> Self;
 
     #[allow(unused_variables)]
     fn resize(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, sc_desc: &wgpu::SwapChainDescriptor) {}
 
+    /// Camera whose aspect ratio follows the window, updated before `resize` is called.
+    fn camera(&mut self) -> Option<&mut math::Camera> {
+        None
+    }
+
     fn update(&mut self, event: WindowEvent);
 
-    fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: &wgpu::SwapChainTexture, spawner: &impl futures::task::LocalSpawn);
+    /// Interval of `fixed_update`.
+    fn fixed_timestep() -> Duration {
+        Duration::from_secs_f64(1.0 / 60.0)
+    }
+
+    /// Called `FrameTime::fixed_steps` times per frame before `tick`, with the fixed timestep in seconds.
+    #[allow(unused_variables)]
+    fn fixed_update(&mut self, dt: f32) {}
+
+    /// Called once per frame before `compute` and `render`.
+    #[allow(unused_variables)]
+    fn tick(&mut self, time: &time::FrameTime) {}
+
+    /// Records compute passes, submitted every frame ahead of `render`.
+    #[allow(unused_variables)]
+    fn compute(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder) {}
+
+    fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: &wgpu::TextureView, spawner: &impl futures::task::LocalSpawn);
+}
+
+fn tick<A: App, C: time::Clock>(app: &mut A, timer: &mut time::FrameTimer<C>) {
+    let time = timer.tick();
+    let step = timer.fixed_step().as_secs_f32();
+    for _ in 0..time.fixed_steps {
+        app.fixed_update(step);
+    }
+    app.tick(&time);
+}
+
+fn compute<A: App>(app: &mut A, device: &wgpu::Device, queue: &wgpu::Queue) {
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("compute") });
+    app.compute(device, queue, &mut encoder);
+    queue.submit(Some(encoder.finish()));
+}
+
+/// Decides when to redraw under a `RunConfig`.
+struct Pacer {
+    redraw: Redraw,
+    interval: Option<Duration>,
+    last: Option<Instant>,
+    dirty: bool,
+    idle: bool,
+}
+
+impl Pacer {
+    fn new(config: &RunConfig) -> Self {
+        Self {
+            redraw: config.redraw,
+            interval: config.max_fps.filter(|fps| *fps > 0.0).map(|fps| Duration::from_secs_f32(1.0 / fps)),
+            last: None,
+            dirty: true,
+            idle: false,
+        }
+    }
+
+    /// Something changed that should be drawn.
+    fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Whether to redraw now, and how long to wait before asking again.
+    /// Idle waits wake up at `next_check`, if any.
+    fn poll(&mut self, now: Instant, needs_redraw: bool, next_check: Option<Instant>) -> (bool, ControlFlow) {
+        if self.redraw == Redraw::OnDemand && !self.dirty && !needs_redraw {
+            self.idle = true;
+            return (false, next_check.map_or(ControlFlow::Wait, ControlFlow::WaitUntil));
+        }
+        if let (Some(interval), Some(last)) = (self.interval, self.last) {
+            if now < last + interval {
+                return (false, ControlFlow::WaitUntil(last + interval));
+            }
+        }
+        self.idle = false;
+        (true, ControlFlow::Poll)
+    }
+
+    fn drawn(&mut self, now: Instant) {
+        self.last = Some(now);
+        self.dirty = false;
+    }
+}
+
+/// Tells clicks apart from drags.
+#[derive(Default)]
+struct ClickTracker {
+    cursor: (f32, f32),
+    pressed: Option<(f32, f32)>,
+}
+
+impl ClickTracker {
+    const SLOP: f32 = 4.0;
+
+    fn process(&mut self, event: &WindowEvent) -> Option<(f32, f32)> {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = (position.x as f32, position.y as f32);
+            }
+            WindowEvent::MouseInput { state, button: event::MouseButton::Left, .. } => match state {
+                event::ElementState::Pressed => self.pressed = Some(self.cursor),
+                event::ElementState::Released => {
+                    let (x, y) = self.pressed.take()?;
+                    let (dx, dy) = (self.cursor.0 - x, self.cursor.1 - y);
+                    if dx * dx + dy * dy <= Self::SLOP * Self::SLOP {
+                        return Some(self.cursor);
+                    }
+                }
+            },
+            _ => {}
+        }
+        None
+    }
 }
 
 struct Setup {
@@ -100,56 +269,117 @@ fn start<A: App>(Setup {window,event_loop,instance,surface,adapter,device,queue}
         (local_pool, spawner)
     };
 
+    let config = A::run_config();
     let mut sc_desc = wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
         // TODO: Allow srgb unconditionally
         format: wgpu::TextureFormat::Bgra8UnormSrgb,
         width: window.inner_size().width,
         height: window.inner_size().height,
-        present_mode: wgpu::PresentMode::Mailbox,
+        present_mode: config.present_mode,
     };
     let mut swap_chain = device.create_swap_chain(&surface, &sc_desc);
+    match config.present_mode {
+        wgpu::PresentMode::Fifo => println!("presenting with Fifo"),
+        mode => println!("presenting with {:?}, or Fifo where the surface doesn't support it", mode),
+    }
 
     let mut app = A::init(&device, &queue, &sc_desc);
 
-    let mut last_update_inst = Instant::now();
+    let start_inst = Instant::now();
+    let mut pacer = Pacer::new(&config);
+    let mut focused = true;
+    let mut timer = time::FrameTimer::new(time::SystemClock::new(), A::fixed_timestep());
+    let mut screenshot = false;
+    let mut clicks = ClickTracker::default();
 
     event_loop.run(move |event, _, control_flow| {
         let _ = (&instance, &adapter); // force ownership by the closure
-        *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(10));
 
         match event {
             Event::MainEventsCleared => {
-                if last_update_inst.elapsed() > Duration::from_millis(20) {
+                pool.run_until_stalled();
+                if *control_flow == ControlFlow::Exit {
+                    return;
+                }
+                let idle = pacer.is_idle();
+                let (redraw, next) = pacer.poll(Instant::now(), app.needs_redraw(), app.next_check());
+                if redraw {
+                    if idle {
+                        timer.skip();
+                    }
                     window.request_redraw();
-                    last_update_inst = Instant::now();
                 }
-                pool.run_until_stalled();
+                *control_flow = next;
             }
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(size) => {
-                    sc_desc.width = if size.width == 0 { 1 } else { size.width };
-                    sc_desc.height = if size.height == 0 { 1 } else { size.height };
-                    app.resize(&device, &queue,&sc_desc);
-                    swap_chain = device.create_swap_chain(&surface, &sc_desc);
-                }
-                WindowEvent::KeyboardInput {
-                    input:
-                        event::KeyboardInput {
-                            virtual_keycode: Some(event::VirtualKeyCode::Escape),
-                            state: event::ElementState::Pressed,
-                            ..
-                        },
-                    ..
+            Event::WindowEvent { event, .. } => {
+                pacer.invalidate();
+                if let WindowEvent::Focused(focus) = event {
+                    focused = focus;
                 }
-                | WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit;
+                if let Some((x, y)) = clicks.process(&event) {
+                    app.click(x, y);
                 }
-                _ => {
-                    app.update(event);
+                match event {
+                    WindowEvent::Resized(size) => {
+                        sc_desc.width = if size.width == 0 { 1 } else { size.width };
+                        sc_desc.height = if size.height == 0 { 1 } else { size.height };
+                        if let Some(camera) = app.camera() {
+                            camera.set_viewport(sc_desc.width, sc_desc.height);
+                        }
+                        app.resize(&device, &queue,&sc_desc);
+                        swap_chain = device.create_swap_chain(&surface, &sc_desc);
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            event::KeyboardInput {
+                                virtual_keycode: Some(event::VirtualKeyCode::Escape),
+                                state: event::ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    }
+                    | WindowEvent::CloseRequested => {
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    WindowEvent::KeyboardInput {
+                        input:
+                            event::KeyboardInput {
+                                virtual_keycode: Some(event::VirtualKeyCode::F12),
+                                state: event::ElementState::Pressed,
+                                ..
+                            },
+                        ..
+                    } => {
+                        screenshot = true;
+                    }
+                    _ => {
+                        app.update(event);
+                    }
                 }
-            },
+            }
             Event::RedrawRequested(_) => {
+                tick(&mut app, &mut timer);
+                compute(&mut app, &device, &queue);
+
+                if screenshot {
+                    screenshot = false;
+                    // swap chain images can't be copied from, so this frame only goes offscreen
+                    // and the window shows it from the next redraw on
+                    let (target, view) = capture::create_target(&device, &sc_desc);
+                    app.render(&device, &queue, &view, &spawner);
+                    let path = format!("screenshot-{}.png", Instant::now().duration_since(start_inst).as_millis());
+                    match capture::read_texture(&device, &queue, &target, sc_desc.format, sc_desc.width, sc_desc.height)
+                        .and_then(|frame| frame.save(std::path::Path::new(&path)))
+                    {
+                        Ok(()) => println!("saved {}", path),
+                        Err(e) => eprintln!("failed to save {}: {}", path, e),
+                    }
+                    pacer.drawn(Instant::now());
+                    pacer.invalidate();
+                    return;
+                }
+
                 let frame = match swap_chain.get_current_frame() {
                     Ok(frame) => frame,
                     Err(_) => {
@@ -157,10 +387,14 @@ fn start<A: App>(Setup {window,event_loop,instance,surface,adapter,device,queue}
                         swap_chain.get_current_frame().expect("Failed to acquire next swap chain texture!")
                     }
                 };
-
-                app.render(&device, &queue, &frame.output, &spawner);
+                app.render(&device, &queue, &frame.output.view, &spawner);
+                pacer.drawn(Instant::now());
             },
             Event::DeviceEvent{event, ..} => {
+                // device events arrive for the whole desktop, only redraw for them while focused
+                if focused {
+                    pacer.invalidate();
+                }
                 match event {
                     DeviceEvent::MouseMotion {delta} => {
                         app.mouse_move(delta.0 as f32, delta.1 as f32);
@@ -186,4 +420,177 @@ fn start<A: App>(Setup {window,event_loop,instance,surface,adapter,device,queue}
 pub fn run<A:App>(title: &str) {
     let setup = futures::executor::block_on(setup::<A>(title));
     start::<A>(setup);
-}
\ No newline at end of file
+}
+
+pub struct Headless<A: App> {
+    _instance: wgpu::Instance,
+    _adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    sc_desc: wgpu::SwapChainDescriptor,
+    target: wgpu::Texture,
+    view: wgpu::TextureView,
+    pool: futures::executor::LocalPool,
+    spawner: futures::executor::LocalSpawner,
+    /// Only moves with `advance`, so frames are reproducible.
+    timer: time::FrameTimer<time::ManualClock>,
+    app: A,
+}
+
+async fn request_headless_adapter() -> Option<(wgpu::Instance, wgpu::Adapter)> {
+    // Software rasterizers such as lavapipe or SwiftShader show up as regular
+    // primary adapters, secondary backends are only tried when none is found.
+    for backends in &[wgpu::BackendBit::PRIMARY, wgpu::BackendBit::SECONDARY] {
+        let instance = wgpu::Instance::new(*backends);
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::Default,
+                compatible_surface: None,
+            })
+            .await;
+        if let Some(adapter) = adapter {
+            return Some((instance, adapter));
+        }
+    }
+    None
+}
+
+impl<A: App> Headless<A> {
+    pub async fn new(width: u32, height: u32) -> Option<Self> {
+        let (instance, adapter) = request_headless_adapter().await?;
+
+        let optional_features = A::optional_features();
+        let required_features = A::required_features();
+        let adapter_features = adapter.features();
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: (optional_features & adapter_features) | required_features,
+                    limits: A::required_limits(),
+                    shader_validation: true,
+                },
+                None,
+            )
+            .await
+            .ok()?;
+
+        let sc_desc = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+        let (target, view) = capture::create_target(&device, &sc_desc);
+
+        let pool = futures::executor::LocalPool::new();
+        let spawner = pool.spawner();
+
+        let app = A::init(&device, &queue, &sc_desc);
+
+        Some(Headless {
+            _instance: instance,
+            _adapter: adapter,
+            device,
+            queue,
+            sc_desc,
+            target,
+            view,
+            pool,
+            spawner,
+            timer: time::FrameTimer::new(time::ManualClock::default(), A::fixed_timestep()),
+            app,
+        })
+    }
+
+    pub fn app(&mut self) -> &mut A {
+        &mut self.app
+    }
+
+    pub fn device(&self) -> &wgpu::Device {
+        &self.device
+    }
+
+    pub fn queue(&self) -> &wgpu::Queue {
+        &self.queue
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.sc_desc.width = width.max(1);
+        self.sc_desc.height = height.max(1);
+        if let Some(camera) = self.app.camera() {
+            camera.set_viewport(self.sc_desc.width, self.sc_desc.height);
+        }
+        self.app.resize(&self.device, &self.queue, &self.sc_desc);
+        let (target, view) = capture::create_target(&self.device, &self.sc_desc);
+        self.target = target;
+        self.view = view;
+    }
+
+    pub fn click(&mut self, x: f32, y: f32) {
+        self.app.click(x, y);
+    }
+
+    /// Moves the clock of the next `render` on by `dt`.
+    pub fn advance(&mut self, dt: Duration) {
+        self.timer.clock().advance(dt);
+    }
+
+    pub fn render(&mut self) {
+        tick(&mut self.app, &mut self.timer);
+        compute(&mut self.app, &self.device, &self.queue);
+        self.app.render(&self.device, &self.queue, &self.view, &self.spawner);
+        self.pool.run_until_stalled();
+    }
+
+    pub fn read_frame(&self) -> capture::Frame {
+        capture::read_texture(&self.device, &self.queue, &self.target, self.sc_desc.format, self.sc_desc.width, self.sc_desc.height)
+            .expect("Failed to read back headless frame!")
+    }
+}
+
+pub fn run_headless<A: App>(width: u32, height: u32) -> Option<Headless<A>> {
+    futures::executor::block_on(Headless::<A>::new(width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn on_demand_waits_for_changes() {
+        let mut pacer = Pacer::new(&RunConfig { redraw: Redraw::OnDemand, ..RunConfig::default() });
+        let now = Instant::now();
+        assert_eq!(pacer.poll(now, false, None), (true, ControlFlow::Poll));
+        pacer.drawn(now);
+        assert_eq!(pacer.poll(now, false, None), (false, ControlFlow::Wait));
+        assert!(pacer.is_idle());
+        assert_eq!(pacer.poll(now, true, None), (true, ControlFlow::Poll));
+        pacer.drawn(now);
+        pacer.invalidate();
+        assert_eq!(pacer.poll(now, false, None), (true, ControlFlow::Poll));
+    }
+
+    #[test]
+    fn max_fps_spaces_frames() {
+        let mut pacer = Pacer::new(&RunConfig { max_fps: Some(8.0), ..RunConfig::default() });
+        let now = Instant::now();
+        assert_eq!(pacer.poll(now, false, None), (true, ControlFlow::Poll));
+        pacer.drawn(now);
+        let due = now + Duration::from_millis(125);
+        assert_eq!(pacer.poll(now + Duration::from_millis(30), false, None), (false, ControlFlow::WaitUntil(due)));
+        assert_eq!(pacer.poll(due, false, None), (true, ControlFlow::Poll));
+    }
+
+    #[test]
+    fn idle_waits_until_the_next_check() {
+        let mut pacer = Pacer::new(&RunConfig { redraw: Redraw::OnDemand, ..RunConfig::default() });
+        let now = Instant::now();
+        pacer.poll(now, false, None);
+        pacer.drawn(now);
+        let check = now + Duration::from_millis(250);
+        assert_eq!(pacer.poll(now, false, Some(check)), (false, ControlFlow::WaitUntil(check)));
+        assert_eq!(pacer.poll(check, true, Some(check)), (true, ControlFlow::Poll));
+    }
+}
@@ -0,0 +1,268 @@
+use super::{Node, Scene};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    CubicSpline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    Translation,
+    Rotation,
+    Scale,
+    /// Morph target weights of the node's mesh.
+    Weights,
+}
+
+/// Keyframes of one property. Cubic spline samplers store `[in tangent, value, out tangent]` per key.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    /// Seconds, ascending.
+    pub times: Vec<f32>,
+    /// `width` floats per value: xyz for translations and scales, xyzw quaternions for
+    /// rotations and one per morph target for weights.
+    pub values: Vec<f32>,
+    pub width: usize,
+    pub interpolation: Interpolation,
+}
+
+impl Sampler {
+    pub fn duration(&self) -> f32 {
+        self.times.last().cloned().unwrap_or(0.0)
+    }
+
+    fn element(&self, index: usize) -> &[f32] {
+        &self.values[index * self.width..(index + 1) * self.width]
+    }
+
+    fn value(&self, key: usize) -> &[f32] {
+        match self.interpolation {
+            Interpolation::CubicSpline => self.element(key * 3 + 1),
+            _ => self.element(key),
+        }
+    }
+
+    /// Value at `time`, held before the first and after the last key. Quaternions are
+    /// interpolated along the shorter arc when `rotation` is set.
+    pub fn sample(&self, time: f32, rotation: bool) -> Option<Vec<f32>> {
+        let stride = if self.interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+        let keys = self.times.len().min(self.values.len() / (self.width * stride).max(1));
+        if keys == 0 || self.width == 0 {
+            return None;
+        }
+        let next = match self.times[..keys].iter().position(|t| *t > time) {
+            Some(0) => return Some(self.value(0).to_vec()),
+            Some(next) => next,
+            None => return Some(self.value(keys - 1).to_vec()),
+        };
+        let (key, t0, t1) = (next - 1, self.times[next - 1], self.times[next]);
+        let dt = t1 - t0;
+        let s = if dt > 0.0 { (time - t0) / dt } else { 0.0 };
+
+        Some(match self.interpolation {
+            Interpolation::Step => self.value(key).to_vec(),
+            Interpolation::Linear if rotation && self.width == 4 => {
+                let quat = |v: &[f32]| glam::Quat::from_xyzw(v[0], v[1], v[2], v[3]).normalize();
+                let (a, b) = (quat(self.value(key)), quat(self.value(next)));
+                // q and -q are the same rotation, pick the one closer to a
+                let b = if a.dot(b) < 0.0 { -b } else { b };
+                // glam's slerp only approximates the normalization
+                let q = a.slerp(b, s).normalize();
+                vec![q.x, q.y, q.z, q.w]
+            }
+            Interpolation::Linear => {
+                self.value(key).iter().zip(self.value(next)).map(|(a, b)| a + (b - a) * s).collect()
+            }
+            Interpolation::CubicSpline => {
+                let (s2, s3) = (s * s, s * s * s);
+                let p0 = self.value(key);
+                let m0 = self.element(key * 3 + 2);
+                let p1 = self.value(next);
+                let m1 = self.element(next * 3);
+                let value: Vec<f32> = (0..self.width).map(|i| {
+                    p0[i] * (2.0 * s3 - 3.0 * s2 + 1.0)
+                        + m0[i] * dt * (s3 - 2.0 * s2 + s)
+                        + p1[i] * (-2.0 * s3 + 3.0 * s2)
+                        + m1[i] * dt * (s3 - s2)
+                }).collect();
+                if rotation {
+                    let length = value.iter().map(|v| v * v).sum::<f32>().sqrt();
+                    value.into_iter().map(|v| v / length).collect()
+                } else {
+                    value
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Channel {
+    /// Index into `Scene::nodes`.
+    pub node: usize,
+    pub property: Property,
+    pub sampler: Sampler,
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<Channel>,
+    /// Seconds until the last key of any channel.
+    pub duration: f32,
+}
+
+impl Animation {
+    pub fn new(name: String, channels: Vec<Channel>) -> Self {
+        let duration = channels.iter().map(|c| c.sampler.duration()).fold(0.0, f32::max);
+        Self { name, channels, duration }
+    }
+
+    /// Poses the animated nodes at `time`, their world transforms are updated by
+    /// `Scene::update_transforms`.
+    pub fn apply(&self, time: f32, nodes: &mut [Node]) {
+        for channel in &self.channels {
+            let node = match nodes.get_mut(channel.node) {
+                Some(node) => node,
+                None => continue,
+            };
+            let rotation = channel.property == Property::Rotation;
+            let value = match channel.sampler.sample(time, rotation) {
+                Some(value) => value,
+                None => continue,
+            };
+            match (channel.property, value.as_slice()) {
+                (Property::Translation, [x, y, z]) => node.translation = glam::vec3(*x, *y, *z),
+                (Property::Rotation, [x, y, z, w]) => node.rotation = glam::Quat::from_xyzw(*x, *y, *z, *w),
+                (Property::Scale, [x, y, z]) => node.scale = glam::vec3(*x, *y, *z),
+                (Property::Weights, _) => node.weights = value,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Playback state of one of a scene's animations.
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    /// Index into `Scene::animations`.
+    pub animation: usize,
+    pub time: f32,
+    pub speed: f32,
+    /// Wraps around at the end instead of holding the last pose.
+    pub looping: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new(animation: usize) -> Self {
+        Self {
+            animation,
+            time: 0.0,
+            speed: 1.0,
+            looping: true,
+        }
+    }
+
+    /// Advances by `dt` seconds and poses the scene.
+    pub fn update(&mut self, scene: &mut Scene, dt: f32) {
+        self.seek(scene, self.time + dt * self.speed);
+    }
+
+    /// Poses the scene at `time`, e.g. of a `time::Timeline`, wrapped or clamped to the animation.
+    pub fn seek(&mut self, scene: &mut Scene, time: f32) {
+        let animation = match scene.animations.get(self.animation) {
+            Some(animation) => animation,
+            None => return,
+        };
+        if self.looping && animation.duration > 0.0 {
+            self.time = time.rem_euclid(animation.duration);
+        } else {
+            self.time = time.max(0.0).min(animation.duration);
+        }
+        animation.apply(self.time, &mut scene.nodes);
+        scene.update_transforms();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler(interpolation: Interpolation, values: &[f32]) -> Sampler {
+        Sampler {
+            times: vec![1.0, 3.0],
+            values: values.to_vec(),
+            width: 1,
+            interpolation,
+        }
+    }
+
+    #[test]
+    fn linear_and_step_hold_outside_the_keys() {
+        let linear = sampler(Interpolation::Linear, &[2.0, 4.0]);
+        assert_eq!(linear.sample(0.0, false), Some(vec![2.0]));
+        assert_eq!(linear.sample(2.0, false), Some(vec![3.0]));
+        assert_eq!(linear.sample(5.0, false), Some(vec![4.0]));
+
+        let step = sampler(Interpolation::Step, &[2.0, 4.0]);
+        assert_eq!(step.sample(2.9, false), Some(vec![2.0]));
+        assert_eq!(step.sample(3.0, false), Some(vec![4.0]));
+    }
+
+    #[test]
+    fn cubic_spline_uses_tangents() {
+        // flat tangents ease in and out, so the middle is still halfway
+        let flat = sampler(Interpolation::CubicSpline, &[0.0, 2.0, 0.0, 0.0, 4.0, 0.0]);
+        assert_eq!(flat.sample(2.0, false), Some(vec![3.0]));
+        assert!(flat.sample(1.5, false).unwrap()[0] < 2.5);
+
+        // matching slopes reproduce the line
+        let line = sampler(Interpolation::CubicSpline, &[1.0, 2.0, 1.0, 1.0, 4.0, 1.0]);
+        assert!((line.sample(1.5, false).unwrap()[0] - 2.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn weights_interpolate_per_target() {
+        let weights = Sampler {
+            times: vec![0.0, 1.0],
+            values: vec![0.0, 1.0, 1.0, 0.0],
+            width: 2,
+            interpolation: Interpolation::Linear,
+        };
+        assert_eq!(weights.sample(0.25, false), Some(vec![0.25, 0.75]));
+    }
+
+    #[test]
+    fn rotations_slerp() {
+        let quarter = glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        let rotation = Sampler {
+            times: vec![0.0, 1.0],
+            values: vec![0.0, 0.0, 0.0, 1.0, quarter.x, quarter.y, quarter.z, quarter.w],
+            width: 4,
+            interpolation: Interpolation::Linear,
+        };
+        let half = glam::Quat::from_slice_unaligned(&rotation.sample(0.5, true).unwrap());
+        let expected = glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_4);
+        assert!(half.dot(expected).abs() > 0.9999);
+    }
+
+    #[test]
+    fn apply_poses_nodes() {
+        let animation = Animation::new("move".to_string(), vec![Channel {
+            node: 0,
+            property: Property::Translation,
+            sampler: Sampler {
+                times: vec![0.0, 2.0],
+                values: vec![0.0, 0.0, 0.0, 2.0, 0.0, 0.0],
+                width: 3,
+                interpolation: Interpolation::Linear,
+            },
+        }]);
+        assert_eq!(animation.duration, 2.0);
+        let mut nodes = vec![Node::default()];
+        animation.apply(0.5, &mut nodes);
+        assert_eq!(nodes[0].translation, glam::vec3(0.5, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,99 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Gltf(gltf::Error),
+    Io(std::path::PathBuf, std::io::Error),
+    Base64(base64::DecodeError),
+    Image(image::ImageError),
+    /// A `data:` URI that isn't base64 encoded.
+    UnsupportedUri(String),
+    MissingBlob,
+    BufferLength {
+        buffer: usize,
+        expected: usize,
+        actual: usize,
+    },
+    MissingPositions {
+        mesh: String,
+        primitive: usize,
+    },
+    /// An index past the end of the primitive's vertices.
+    IndexOutOfRange {
+        mesh: String,
+        primitive: usize,
+        index: usize,
+        count: usize,
+    },
+    /// A skin used by a mesh has more joints than `pipeline::MAX_JOINTS`.
+    TooManyJoints {
+        skin: String,
+        joints: usize,
+        max: usize,
+    },
+    JointOutOfRange {
+        mesh: String,
+        joint: usize,
+        joints: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Gltf(e) => write!(f, "invalid glTF: {}", e),
+            Error::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Error::Base64(e) => write!(f, "invalid base64 data uri: {}", e),
+            Error::Image(e) => write!(f, "failed to decode image: {}", e),
+            Error::UnsupportedUri(uri) => write!(f, "unsupported uri {}", uri),
+            Error::MissingBlob => write!(f, "glb binary chunk is missing"),
+            Error::BufferLength { buffer, expected, actual } => {
+                write!(f, "buffer {} has {} bytes, expected {}", buffer, actual, expected)
+            }
+            Error::MissingPositions { mesh, primitive } => {
+                write!(f, "primitive {} of mesh {} has no positions", primitive, mesh)
+            }
+            Error::IndexOutOfRange { mesh, primitive, index, count } => {
+                write!(f, "primitive {} of mesh {} uses vertex {} of {}", primitive, mesh, index, count)
+            }
+            Error::TooManyJoints { skin, joints, max } => {
+                write!(f, "skin {} has {} joints, at most {} are supported", skin, joints, max)
+            }
+            Error::JointOutOfRange { mesh, joint, joints } => {
+                write!(f, "mesh {} uses joint {} of a skin with {} joints", mesh, joint, joints)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Gltf(e) => Some(e),
+            Error::Io(_, e) => Some(e),
+            Error::Base64(e) => Some(e),
+            Error::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for Error {
+    fn from(e: gltf::Error) -> Self {
+        Error::Gltf(e)
+    }
+}
+
+impl From<base64::DecodeError> for Error {
+    fn from(e: base64::DecodeError) -> Self {
+        Error::Base64(e)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(e: image::ImageError) -> Self {
+        Error::Image(e)
+    }
+}
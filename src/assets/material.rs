@@ -0,0 +1,203 @@
+use bytemuck::{Pod, Zeroable};
+use super::{Result, Texture};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+/// glTF metallic-roughness material, texture slots index into `Scene::textures`.
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub base_color_factor: glam::Vec4,
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<usize>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: glam::Vec3,
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color_factor: glam::Vec4::one(),
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: glam::Vec3::zero(),
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct MaterialUniform {
+    base_color_factor: [f32; 4],
+    emissive_factor: [f32; 4],
+    // metallic, roughness, normal scale, occlusion strength
+    factors: [f32; 4],
+    // cutoff, mask enabled
+    alpha: [f32; 4],
+}
+
+unsafe impl Zeroable for MaterialUniform {}
+unsafe impl Pod for MaterialUniform {}
+
+/// Stand-ins bound for material maps a glTF material doesn't provide.
+pub struct DefaultTextures {
+    pub white: Texture,
+    pub normal: Texture,
+}
+
+impl DefaultTextures {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self {
+            white: Texture::solid(device, queue, [255, 255, 255, 255]),
+            normal: Texture::solid(device, queue, [128, 128, 255, 255]),
+        }
+    }
+}
+
+pub struct GpuMaterial {
+    pub uniform_buffer: wgpu::Buffer,
+    pub bind_group: wgpu::BindGroup,
+}
+
+pub fn material_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    let mut entries = vec![wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStage::FRAGMENT,
+        ty: wgpu::BindingType::UniformBuffer {
+            dynamic: false,
+            min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<MaterialUniform>() as _),
+        },
+        count: None,
+    }];
+    // base color, metallic-roughness, normal, occlusion, emissive
+    for slot in 0..5 {
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 1 + slot * 2,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::SampledTexture {
+                dimension: wgpu::TextureViewDimension::D2,
+                component_type: wgpu::TextureComponentType::Float,
+                multisampled: false,
+            },
+            count: None,
+        });
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: 2 + slot * 2,
+            visibility: wgpu::ShaderStage::FRAGMENT,
+            ty: wgpu::BindingType::Sampler { comparison: false },
+            count: None,
+        });
+    }
+
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("material"),
+        entries: &entries,
+    })
+}
+
+impl Material {
+    pub fn from_gltf(gm: &gltf::Material, textures: &mut impl FnMut(gltf::texture::Texture, bool) -> Result<usize>) -> Result<Self> {
+        let pbr = gm.pbr_metallic_roughness();
+        Ok(Material {
+            name: gm.name().unwrap_or_default().to_string(),
+            base_color_factor: glam::Vec4::from(pbr.base_color_factor()),
+            base_color_texture: pbr.base_color_texture().map(|t| textures(t.texture(), true)).transpose()?,
+            metallic_factor: pbr.metallic_factor(),
+            roughness_factor: pbr.roughness_factor(),
+            metallic_roughness_texture: pbr.metallic_roughness_texture().map(|t| textures(t.texture(), false)).transpose()?,
+            normal_texture: gm.normal_texture().map(|t| textures(t.texture(), false)).transpose()?,
+            normal_scale: gm.normal_texture().map(|t| t.scale()).unwrap_or(1.0),
+            occlusion_texture: gm.occlusion_texture().map(|t| textures(t.texture(), false)).transpose()?,
+            occlusion_strength: gm.occlusion_texture().map(|t| t.strength()).unwrap_or(1.0),
+            emissive_factor: glam::Vec3::from(gm.emissive_factor()),
+            emissive_texture: gm.emissive_texture().map(|t| textures(t.texture(), true)).transpose()?,
+            alpha_mode: match gm.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+            },
+            alpha_cutoff: gm.alpha_cutoff(),
+            double_sided: gm.double_sided(),
+        })
+    }
+
+    pub fn upload(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        textures: &[Arc<Texture>],
+        defaults: &DefaultTextures,
+    ) -> GpuMaterial {
+        use wgpu::util::DeviceExt;
+
+        let uniform = MaterialUniform {
+            base_color_factor: self.base_color_factor.into(),
+            emissive_factor: self.emissive_factor.extend(0.0).into(),
+            factors: [self.metallic_factor, self.roughness_factor, self.normal_scale, self.occlusion_strength],
+            alpha: [self.alpha_cutoff, if self.alpha_mode == AlphaMode::Mask { 1.0 } else { 0.0 }, 0.0, 0.0],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("material uniform"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsage::UNIFORM,
+        });
+
+        let slots = [
+            self.base_color_texture.map(|i| &*textures[i]).unwrap_or(&defaults.white),
+            self.metallic_roughness_texture.map(|i| &*textures[i]).unwrap_or(&defaults.white),
+            self.normal_texture.map(|i| &*textures[i]).unwrap_or(&defaults.normal),
+            self.occlusion_texture.map(|i| &*textures[i]).unwrap_or(&defaults.white),
+            self.emissive_texture.map(|i| &*textures[i]).unwrap_or(&defaults.white),
+        ];
+
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+        }];
+        for (slot, texture) in slots.iter().enumerate() {
+            let slot = slot as u32;
+            entries.push(wgpu::BindGroupEntry {
+                binding: 1 + slot * 2,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 2 + slot * 2,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            });
+        }
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("material"),
+            layout,
+            entries: &entries,
+        });
+
+        GpuMaterial { uniform_buffer, bind_group }
+    }
+}
@@ -0,0 +1,374 @@
+use wgpu::util::DeviceExt;
+use bytemuck::{Pod, Zeroable};
+use crate::math;
+
+// Interleaved layout bound by `pipeline::VERTEX_ATTRIBUTES`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct VertexData {
+    position: glam::Vec3,
+    normal: glam::Vec3,
+    texcoord: glam::Vec2,
+}
+
+unsafe impl Zeroable for VertexData {}
+unsafe impl Pod for VertexData {}
+
+// Second vertex buffer of skinned meshes, bound by `pipeline::SKIN_ATTRIBUTES`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SkinVertexData {
+    joints: [u32; 4],
+    weights: [f32; 4],
+}
+
+unsafe impl Zeroable for SkinVertexData {}
+unsafe impl Pod for SkinVertexData {}
+
+/// Offsets a `MeshData` moves by at weight 1. Attributes the target doesn't move are empty.
+/// Tangent offsets aren't loaded, tangents aren't uploaded yet.
+#[derive(Debug, Clone, Default)]
+pub struct MorphTarget {
+    pub positions: Vec<glam::Vec3>,
+    pub normals: Vec<glam::Vec3>,
+}
+
+/// CPU geometry of one primitive. Optional attributes are empty or have one entry per position.
+#[derive(Debug, Clone)]
+pub struct MeshData {
+    pub positions: Vec<glam::Vec3>,
+    pub normals: Vec<glam::Vec3>,
+    pub texcoords: Vec<glam::Vec2>,
+    /// xyz tangent and w the bitangent sign.
+    pub tangents: Vec<glam::Vec4>,
+    /// Linear RGBA.
+    pub colors: Vec<glam::Vec4>,
+    /// Indices into `Skin::joints`.
+    pub joints: Vec<[u16; 4]>,
+    pub weights: Vec<glam::Vec4>,
+    pub targets: Vec<MorphTarget>,
+    pub indices: Vec<u32>,
+    pub mode: wgpu::PrimitiveTopology,
+}
+
+impl Default for MeshData {
+    fn default() -> Self {
+        Self {
+            positions: Vec::new(),
+            normals: Vec::new(),
+            texcoords: Vec::new(),
+            tangents: Vec::new(),
+            colors: Vec::new(),
+            joints: Vec::new(),
+            weights: Vec::new(),
+            targets: Vec::new(),
+            indices: Vec::new(),
+            mode: wgpu::PrimitiveTopology::TriangleList,
+        }
+    }
+}
+
+/// Vertex and index buffers of an uploaded `MeshData`.
+pub struct GpuMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    /// Joints and weights, for meshes that have both.
+    pub skin_buffer: Option<wgpu::Buffer>,
+    pub count: usize,
+}
+
+impl MeshData {
+    pub fn aabb(&self) -> math::Aabb {
+        math::Aabb::from_points(self.positions.iter().cloned())
+    }
+
+    /// Vertex index triples, points and lines have none.
+    pub fn triangles(&self) -> Vec<[u32; 3]> {
+        match self.mode {
+            wgpu::PrimitiveTopology::TriangleList => self.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            // every other strip triangle is wound the other way
+            wgpu::PrimitiveTopology::TriangleStrip => self.indices
+                .windows(3)
+                .enumerate()
+                .map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn vertices(&self) -> Vec<VertexData> {
+        self.positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| VertexData {
+                position: *position,
+                normal: self.normals.get(i).cloned().unwrap_or_else(glam::Vec3::zero),
+                texcoord: self.texcoords.get(i).cloned().unwrap_or_else(glam::Vec2::zero),
+            })
+            .collect()
+    }
+
+    /// `vertices` with the morph targets blended in by `weights`, missing weights count as 0.
+    fn morphed_vertices(&self, weights: &[f32]) -> Vec<VertexData> {
+        let mut vertices = self.vertices();
+        for (target, weight) in self.targets.iter().zip(weights) {
+            if *weight == 0.0 {
+                continue;
+            }
+            for (vertex, delta) in vertices.iter_mut().zip(&target.positions) {
+                vertex.position += *delta * *weight;
+            }
+            for (vertex, delta) in vertices.iter_mut().zip(&target.normals) {
+                vertex.normal += *delta * *weight;
+            }
+        }
+        if !self.targets.is_empty() {
+            for vertex in &mut vertices {
+                if vertex.normal != glam::Vec3::zero() {
+                    vertex.normal = vertex.normal.normalize();
+                }
+            }
+        }
+        vertices
+    }
+
+    pub fn upload(&self, device: &wgpu::Device) -> GpuMesh {
+        let vertices = self.vertices();
+
+        let vertex_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor{
+            label: Some("vertex buffer"),
+            contents: &bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(
+            &wgpu::util::BufferInitDescriptor{
+                label: Some("index buffer"),
+                contents: &bytemuck::cast_slice(&self.indices),
+                usage: wgpu::BufferUsage::INDEX,
+            }
+        );
+
+        let skinned = !self.joints.is_empty() && self.joints.len() == self.positions.len() && self.weights.len() == self.positions.len();
+        let skin_buffer = if skinned {
+            let skin: Vec<SkinVertexData> = self.joints
+                .iter()
+                .zip(&self.weights)
+                .map(|(joints, weights)| SkinVertexData {
+                    joints: [joints[0] as u32, joints[1] as u32, joints[2] as u32, joints[3] as u32],
+                    weights: (*weights).into(),
+                })
+                .collect();
+            Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("skin buffer"),
+                contents: bytemuck::cast_slice(&skin),
+                usage: wgpu::BufferUsage::VERTEX,
+            }))
+        } else {
+            None
+        };
+
+        GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            skin_buffer,
+            count: self.indices.len(),
+        }
+    }
+}
+
+pub struct SubMesh {
+    /// CPU copy, `None` once released.
+    pub data: Option<MeshData>,
+    /// `None` until uploaded.
+    pub gpu: Option<GpuMesh>,
+    pub mode: wgpu::PrimitiveTopology,
+    /// Index into `Scene::materials`.
+    pub material: usize,
+    /// Object space.
+    pub aabb: math::Aabb,
+    pub sphere: math::Sphere,
+}
+
+impl SubMesh {
+    /// Uses `aabb` when the source already declares bounds, computes them otherwise.
+    pub fn new(data: MeshData, material: usize, aabb: Option<math::Aabb>) -> Self {
+        let aabb = aabb.unwrap_or_else(|| data.aabb());
+        let sphere = math::Sphere::around(&aabb, data.positions.iter().cloned());
+        Self {
+            mode: data.mode,
+            data: Some(data),
+            gpu: None,
+            material,
+            aabb,
+            sphere,
+        }
+    }
+
+    /// (Re)creates the GPU buffers from the CPU copy, does nothing once it was released.
+    pub fn upload(&mut self, device: &wgpu::Device) {
+        if let Some(data) = &self.data {
+            self.mode = data.mode;
+            self.gpu = Some(data.upload(device));
+        }
+    }
+
+    /// Blends the morph targets on the CPU into `buffer`, which is created on first use.
+    /// Leaves it `None` for submeshes without targets or CPU data.
+    pub fn morph(&self, device: &wgpu::Device, queue: &wgpu::Queue, buffer: &mut Option<wgpu::Buffer>, weights: &[f32]) {
+        let data = match &self.data {
+            Some(data) if !data.targets.is_empty() => data,
+            _ => return,
+        };
+        let vertices = data.morphed_vertices(weights);
+        match buffer {
+            Some(buffer) => queue.write_buffer(buffer, 0, bytemuck::cast_slice(&vertices)),
+            None => {
+                *buffer = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("morphed vertex buffer"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+                }));
+            }
+        }
+    }
+
+    /// Drops the CPU copy, except of morphed submeshes which need it for blending.
+    pub fn release(&mut self) {
+        if self.data.as_ref().map_or(true, |data| data.targets.is_empty()) {
+            self.data = None;
+        }
+    }
+}
+
+/// Vertex buffers of one instance of a mesh with morph targets, parallel to `Mesh::subs`.
+#[derive(Default)]
+pub struct MorphedMesh {
+    /// `None` for submeshes drawn from their own `GpuMesh`.
+    pub vertex_buffers: Vec<Option<wgpu::Buffer>>,
+    /// Weights currently blended into the vertex buffers.
+    weights: Vec<f32>,
+}
+
+pub struct Mesh {
+    pub name: String,
+    pub subs: Vec<SubMesh>,
+    /// Object space, union of the submeshes.
+    pub aabb: math::Aabb,
+    pub sphere: math::Sphere,
+    /// Triangles of all submeshes for ray queries, in object space.
+    pub bvh: math::Bvh,
+    /// Default morph target weights, for nodes that don't set their own.
+    pub weights: Vec<f32>,
+}
+
+impl Mesh {
+    pub fn new(name: String, subs: Vec<SubMesh>) -> Self {
+        let mut mesh = Self {
+            name,
+            subs,
+            aabb: math::Aabb::empty(),
+            sphere: math::Sphere::empty(),
+            bvh: math::Bvh::default(),
+            weights: Vec::new(),
+        };
+        mesh.rebuild();
+        mesh
+    }
+
+    /// Recomputes the bounds and BVH after the CPU data was edited. Submeshes
+    /// without CPU data keep their bounds but drop out of the BVH.
+    pub fn update_bounds(&mut self) {
+        for sub in &mut self.subs {
+            if let Some(data) = &sub.data {
+                sub.aabb = data.aabb();
+                sub.sphere = math::Sphere::around(&sub.aabb, data.positions.iter().cloned());
+            }
+        }
+        self.rebuild();
+    }
+
+    fn rebuild(&mut self) {
+        let mut triangles = Vec::new();
+        for (primitive, sub) in self.subs.iter().enumerate() {
+            let data = match &sub.data {
+                Some(data) => data,
+                None => continue,
+            };
+            for (index, [a, b, c]) in data.triangles().into_iter().enumerate() {
+                let vertex = |i: u32| data.positions.get(i as usize).cloned();
+                if let (Some(a), Some(b), Some(c)) = (vertex(a), vertex(b), vertex(c)) {
+                    triangles.push(math::Triangle { vertices: [a, b, c], primitive, index });
+                }
+            }
+        }
+        self.aabb = self.subs.iter().fold(math::Aabb::empty(), |aabb, sub| aabb.union(sub.aabb));
+        self.sphere = self.subs.iter().fold(math::Sphere::empty(), |sphere, sub| sphere.union(sub.sphere));
+        self.bvh = math::Bvh::new(triangles);
+    }
+
+    pub fn upload(&mut self, device: &wgpu::Device) {
+        for sub in &mut self.subs {
+            sub.upload(device);
+        }
+    }
+
+    /// Blends the morph targets of all submeshes by `weights` into the buffers of one instance,
+    /// skipped when they didn't change. Bounds and BVH keep the base shape.
+    pub fn morph(&self, device: &wgpu::Device, queue: &wgpu::Queue, morphed: &mut MorphedMesh, weights: &[f32]) {
+        if morphed.vertex_buffers.len() == self.subs.len() && morphed.weights == weights {
+            return;
+        }
+        morphed.vertex_buffers.resize_with(self.subs.len(), || None);
+        for (sub, buffer) in self.subs.iter().zip(&mut morphed.vertex_buffers) {
+            sub.morph(device, queue, buffer, weights);
+        }
+        morphed.weights = weights.to_vec();
+    }
+
+    /// Drops the CPU copies, the bounds and BVH stay valid.
+    pub fn release(&mut self) {
+        for sub in &mut self.subs {
+            sub.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assets::load_meshes;
+    use crate::golden::scene_path;
+
+    #[test]
+    fn load_meshes_without_device() {
+        let meshes = load_meshes(&scene_path("cube")).unwrap();
+        let sub = &meshes[0].subs[0];
+        let data = sub.data.as_ref().unwrap();
+        assert!(sub.gpu.is_none());
+        assert_eq!(data.normals.len(), data.positions.len());
+        assert_eq!(data.triangles().len(), 12);
+        assert!(!meshes[0].bvh.is_empty());
+    }
+
+    #[test]
+    fn load_skinned_meshes_without_device() {
+        let meshes = load_meshes(&scene_path("skinned")).unwrap();
+        let data = meshes[0].subs[0].data.as_ref().unwrap();
+        assert_eq!(data.joints.len(), data.positions.len());
+        assert_eq!(data.weights.len(), data.positions.len());
+        assert_eq!(data.joints[4], [1, 0, 0, 0]);
+        assert_eq!(data.weights[2], glam::vec4(0.5, 0.5, 0.0, 0.0));
+    }
+
+    #[test]
+    fn load_morph_targets_without_device() {
+        let meshes = load_meshes(&scene_path("morph")).unwrap();
+        assert_eq!(meshes[0].weights, vec![0.5]);
+        let data = meshes[0].subs[0].data.as_ref().unwrap();
+        assert_eq!(data.targets.len(), 1);
+        assert_eq!(data.morphed_vertices(&meshes[0].weights)[2].position, glam::vec3(0.0, 1.5, 0.0));
+        let unmorphed: Vec<glam::Vec3> = data.morphed_vertices(&[]).iter().map(|vertex| vertex.position).collect();
+        assert_eq!(unmorphed, data.positions);
+    }
+}
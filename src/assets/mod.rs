@@ -1,116 +1,381 @@
-use wgpu::util::DeviceExt;
-use bytemuck::{Pod, Zeroable};
-
-#[repr(C)]
-#[derive(Debug, Clone, Copy)]
-struct VertexData {
-    position: glam::Vec3,
-    normal: glam::Vec3,
-    texcoord: glam::Vec2,
+use crate::math;
+
+mod animation;
+mod error;
+mod material;
+mod mesh;
+mod scene;
+mod skin;
+mod texture;
+pub use animation::{
+    Animation,AnimationPlayer,Channel,Interpolation,Property,Sampler,
+};
+pub use error::{
+    Error,Result,
+};
+pub use material::{
+    AlphaMode,DefaultTextures,GpuMaterial,Material,material_bind_group_layout,
+};
+pub use mesh::{
+    GpuMesh,Mesh,MeshData,MorphTarget,MorphedMesh,SubMesh,
+};
+pub use scene::{
+    Node,RayHit,Scene,
+};
+pub use skin::Skin;
+pub use texture::{
+    Texture,TextureCache,
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadOptions {
+    /// Keep `SubMesh::data` after the upload instead of freeing it, morphed submeshes always keep it.
+    pub retain_mesh_data: bool,
 }
 
-impl Default for VertexData {
-    fn default() -> Self {
-        Self {
-            position: glam::Vec3::zero(),
-            normal: glam::Vec3::zero(),
-            texcoord: glam::Vec2::zero(),
-        }
-    }
+/// Position bounds declared by the accessor, they are required by the spec but not always present.
+fn accessor_bounds(gp: &gltf::Primitive) -> Option<math::Aabb> {
+    let accessor = gp.get(&gltf::Semantic::Positions)?;
+    let min: [f32; 3] = gltf::json::deserialize::from_value(accessor.min()?).ok()?;
+    let max: [f32; 3] = gltf::json::deserialize::from_value(accessor.max()?).ok()?;
+    Some(math::Aabb::new(min.into(), max.into()))
 }
 
-unsafe impl Zeroable for VertexData {}
-unsafe impl Pod for VertexData {}
+/// Reads a `data:` URI, a `file:` URI or a path relative to the glTF file.
+fn read_uri(base: &std::path::Path, uri: &str) -> Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let encoded = data.split(";base64,").nth(1).ok_or_else(|| Error::UnsupportedUri(uri.to_string()))?;
+        return Ok(base64::decode(encoded)?);
+    }
+    let path = uri_path(base, uri);
+    std::fs::read(&path).map_err(|e| Error::Io(path, e))
+}
 
-pub struct SubMesh {
-    pub count: usize,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub mode: wgpu::PrimitiveTopology,
+fn uri_path(base: &std::path::Path, uri: &str) -> std::path::PathBuf {
+    match uri.strip_prefix("file://").or_else(|| uri.strip_prefix("file:")) {
+        Some(path) => std::path::PathBuf::from(percent_decode(path)),
+        None => base.join(percent_decode(uri)),
+    }
 }
-pub struct Mesh {
-    pub name: String,
-    pub subs: Vec<SubMesh>,
+
+/// Decodes `%XX` escapes, e.g. `my%20texture.png`. Malformed escapes are kept as they are.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| u8::from_str_radix(std::str::from_utf8(h).ok()?, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
-pub struct Scene {
-    pub meshes: Vec<Mesh>,
+fn load_buffers(document: &gltf::Document, base: &std::path::Path, mut blob: Option<Vec<u8>>) -> Result<Vec<gltf::buffer::Data>> {
+    document.buffers().map(|gb| {
+        let mut data = match gb.source() {
+            gltf::buffer::Source::Bin => blob.take().ok_or(Error::MissingBlob)?,
+            gltf::buffer::Source::Uri(uri) => read_uri(base, uri)?,
+        };
+        if data.len() < gb.length() {
+            return Err(Error::BufferLength { buffer: gb.index(), expected: gb.length(), actual: data.len() });
+        }
+        while data.len() % 4 != 0 {
+            data.push(0);
+        }
+        Ok(gltf::buffer::Data(data))
+    }).collect()
 }
 
-pub async fn from_gltf(device: &wgpu::Device, path: &std::path::Path) -> Scene {
-    let (document, buffers, _images) = gltf::import(path).unwrap();
+/// Reads the meshes of a glTF file without touching the GPU, primitives without a
+/// material point one past the document's materials.
+pub fn load_meshes(path: &std::path::Path) -> Result<Vec<Mesh>> {
+    let base = path.parent().unwrap_or_else(|| std::path::Path::new("./"));
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    let buffers = load_buffers(&document, base, blob)?;
+    read_meshes(&document, &buffers, document.materials().len())
+}
 
-    let meshes: Vec<Mesh> = document.meshes().map(|gm| {
+fn read_meshes(document: &gltf::Document, buffers: &[gltf::buffer::Data], default_material: usize) -> Result<Vec<Mesh>> {
+    document.meshes().map(|gm| {
+        let name = gm.name().map(str::to_string).unwrap_or_else(|| format!("mesh {}", gm.index()));
         let subs: Vec<SubMesh> = gm.primitives().map(|gp|{
             let reader = gp.reader(|bf|Some(&buffers[bf.index()]));
-            let positions: Vec<_> = reader
+            let positions: Vec<glam::Vec3> = reader
                     .read_positions()
-                    .map(|iter| iter.collect())
-                    .ok_or(format!("mesh primitive is missing positions"))
-                    .unwrap();
-
-            let mut vertices: Vec<VertexData> = positions
-                .iter()
-                .map(|pos| VertexData {
-                    position: glam::Vec3::from(pos.clone()),
-                    ..Default::default()
-                })
-                .collect();
-
-            if let Some(normals) = reader.read_normals() {
-                for (i, normal) in normals.enumerate() {
-                    vertices[i].normal = glam::Vec3::from(normal.clone());
-                }
-            }
+                    .map(|iter| iter.map(glam::Vec3::from).collect())
+                    .ok_or_else(|| Error::MissingPositions { mesh: name.clone(), primitive: gp.index() })?;
 
-            if let Some(texcoord) = reader.read_tex_coords(0) {
-                for (i, texcoord) in texcoord.into_f32().enumerate() {
-                    vertices[i].texcoord = glam::Vec2::from(texcoord.clone());
-                }
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(i) => i.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            if let Some(index) = indices.iter().find(|i| **i as usize >= positions.len()) {
+                return Err(Error::IndexOutOfRange {
+                    mesh: name.clone(),
+                    primitive: gp.index(),
+                    index: *index as usize,
+                    count: positions.len(),
+                });
             }
+            let (mode, indices) = convert_primitive_mode(gp.mode(), indices);
 
-            let vertex_buffer = device.create_buffer_init(
-                &wgpu::util::BufferInitDescriptor{
-                label: Some("vertex buffer"),
-                contents: &bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-        
-            let indices: Vec<u32> = if let Some(i) = reader.read_indices() {
-                i.into_u32().collect()
-            } else {
-                panic!("model doesn't have indices");
+            let data = MeshData {
+                normals: reader.read_normals().map(|iter| iter.map(glam::Vec3::from).collect()).unwrap_or_default(),
+                texcoords: reader.read_tex_coords(0).map(|iter| iter.into_f32().map(glam::Vec2::from).collect()).unwrap_or_default(),
+                tangents: reader.read_tangents().map(|iter| iter.map(glam::Vec4::from).collect()).unwrap_or_default(),
+                colors: reader.read_colors(0).map(|iter| iter.into_rgba_f32().map(glam::Vec4::from).collect()).unwrap_or_default(),
+                joints: reader.read_joints(0).map(|iter| iter.into_u16().collect()).unwrap_or_default(),
+                weights: reader.read_weights(0).map(|iter| iter.into_f32().map(glam::Vec4::from).collect()).unwrap_or_default(),
+                targets: reader.read_morph_targets().map(|(positions, normals, _)| MorphTarget {
+                    positions: positions.map(|iter| iter.map(glam::Vec3::from).collect()).unwrap_or_default(),
+                    normals: normals.map(|iter| iter.map(glam::Vec3::from).collect()).unwrap_or_default(),
+                }).collect(),
+                positions,
+                indices,
+                mode,
             };
+            let material = gp.material().index().unwrap_or(default_material);
+            Ok(SubMesh::new(data, material, accessor_bounds(&gp)))
+        }).collect::<Result<_>>()?;
+
+        let mut mesh = Mesh::new(name, subs);
+        let targets = mesh.subs.iter().filter_map(|sub| sub.data.as_ref()).map(|data| data.targets.len()).max().unwrap_or(0);
+        mesh.weights = gm.weights().map(<[f32]>::to_vec).unwrap_or_else(|| vec![0.0; targets]);
+        Ok(mesh)
+    }).collect()
+}
+
+pub async fn from_gltf(device: &wgpu::Device, queue: &wgpu::Queue, path: &std::path::Path) -> Result<Scene> {
+    from_gltf_with_cache(device, queue, path, &mut TextureCache::new(), LoadOptions::default()).await
+}
+
+pub async fn from_gltf_with_cache(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    path: &std::path::Path,
+    cache: &mut TextureCache,
+    options: LoadOptions,
+) -> Result<Scene> {
+    let base = path.parent().unwrap_or_else(|| std::path::Path::new("./"));
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path)?;
+    let buffers = load_buffers(&document, base, blob)?;
 
-            let index_buffer = device.create_buffer_init(
-                &wgpu::util::BufferInitDescriptor{
-                    label: Some("index buffer"),
-                    contents: &bytemuck::cast_slice(&indices),
-                    usage: wgpu::BufferUsage::INDEX,
+    let mut textures: Vec<std::sync::Arc<Texture>> = Vec::new();
+    let mut materials: Vec<Material> = document.materials().map(|gm| {
+        Material::from_gltf(&gm, &mut |gt, srgb| {
+            let texture = cache.load(device, queue, &gt, base, &buffers, srgb)?;
+            Ok(match textures.iter().position(|t| std::sync::Arc::ptr_eq(t, &texture)) {
+                Some(index) => index,
+                None => {
+                    textures.push(texture);
+                    textures.len() - 1
                 }
-            );
+            })
+        })
+    }).collect::<Result<_>>()?;
+    // primitives without a material use the glTF default material
+    let default_material = materials.len();
+    materials.push(Material::default());
 
-            SubMesh {
-                count: indices.len(), 
-                vertex_buffer, 
-                index_buffer,
-                mode: get_primitive_mode(gp.mode())
-            }
-        }).collect();
+    let mut meshes = read_meshes(&document, &buffers, default_material)?;
+    let skins = read_skins(&document, &buffers);
+    // checked while the meshes still have their CPU data
+    for gn in document.nodes() {
+        if let (Some(gm), Some(gs)) = (gn.mesh(), gn.skin()) {
+            skins[gs.index()].check(&meshes[gm.index()])?;
+        }
+    }
+    for mesh in &mut meshes {
+        mesh.upload(device);
+        if !options.retain_mesh_data {
+            mesh.release();
+        }
+    }
 
-        Mesh {name: gm.name().unwrap().to_string(), subs}
+    let mut nodes: Vec<Node> = document.nodes().map(|gn| {
+        let (translation, rotation, scale) = gn.transform().decomposed();
+        Node {
+            name: gn.name().unwrap_or_default().to_string(),
+            children: gn.children().map(|c| c.index()).collect(),
+            translation: glam::Vec3::from(translation),
+            rotation: glam::Quat::from_slice_unaligned(&rotation),
+            scale: glam::Vec3::from(scale),
+            mesh: gn.mesh().map(|m| m.index()),
+            skin: gn.skin().map(|s| s.index()),
+            weights: gn.weights().map(<[f32]>::to_vec).unwrap_or_default(),
+            ..Default::default()
+        }
     }).collect();
 
-    Scene{ meshes }
+    for parent in 0..nodes.len() {
+        for child in nodes[parent].children.clone() {
+            nodes[child].parent = Some(parent);
+        }
+    }
+
+    let roots: Vec<usize> = match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(gs) => gs.nodes().map(|n| n.index()).collect(),
+        None if nodes.is_empty() => {
+            // no node hierarchy at all, place every mesh once at the origin
+            nodes = (0..meshes.len()).map(|i| Node {
+                name: meshes[i].name.clone(),
+                mesh: Some(i),
+                ..Default::default()
+            }).collect();
+            (0..nodes.len()).collect()
+        }
+        None => (0..nodes.len()).filter(|i| nodes[*i].parent.is_none()).collect(),
+    };
+
+    let animations = read_animations(&document, &buffers);
+
+    let layout = material_bind_group_layout(device);
+    let default_textures = DefaultTextures::new(device, queue);
+    let gpu_materials = materials.iter().map(|m| m.upload(device, &layout, &textures, &default_textures)).collect();
+
+    let mut scene = Scene{
+        meshes, nodes, roots, materials, gpu_materials, textures, default_textures, skins, animations,
+        aabb: math::Aabb::empty(),
+        sphere: math::Sphere::empty(),
+    };
+    scene.update_transforms();
+    Ok(scene)
+}
+
+fn read_skins(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Vec<Skin> {
+    document.skins().map(|gs| {
+        let joints: Vec<usize> = gs.joints().map(|n| n.index()).collect();
+        let reader = gs.reader(|bf| Some(&buffers[bf.index()]));
+        let mut inverse_bind: Vec<glam::Mat4> = reader
+            .read_inverse_bind_matrices()
+            .map(|iter| iter.map(|m| glam::Mat4::from_cols_array_2d(&m)).collect())
+            .unwrap_or_default();
+        inverse_bind.resize(joints.len(), glam::Mat4::identity());
+        Skin {
+            name: gs.name().unwrap_or_default().to_string(),
+            joints,
+            inverse_bind,
+            skeleton: gs.skeleton().map(|n| n.index()),
+        }
+    }).collect()
 }
 
-fn get_primitive_mode(mode: gltf::mesh::Mode) -> wgpu::PrimitiveTopology {
+fn read_animations(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Vec<Animation> {
+    document.animations().map(|ga| {
+        let channels = ga.channels().filter_map(|gc| {
+            let reader = gc.reader(|bf| Some(&buffers[bf.index()]));
+            let times: Vec<f32> = reader.read_inputs()?.collect();
+            let (property, values): (Property, Vec<f32>) = match reader.read_outputs()? {
+                gltf::animation::util::ReadOutputs::Translations(iter) => (Property::Translation, iter.flatten().collect()),
+                gltf::animation::util::ReadOutputs::Rotations(iter) => (Property::Rotation, iter.into_f32().flatten().collect()),
+                gltf::animation::util::ReadOutputs::Scales(iter) => (Property::Scale, iter.flatten().collect()),
+                gltf::animation::util::ReadOutputs::MorphTargetWeights(iter) => (Property::Weights, iter.into_f32().collect()),
+            };
+            let interpolation = match gc.sampler().interpolation() {
+                gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                gltf::animation::Interpolation::Step => Interpolation::Step,
+                gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+            };
+            let width = match property {
+                Property::Translation | Property::Scale => 3,
+                Property::Rotation => 4,
+                // weights have no accessor type, each key holds one per morph target
+                Property::Weights => {
+                    let stride = if interpolation == Interpolation::CubicSpline { 3 } else { 1 };
+                    values.len() / (times.len() * stride).max(1)
+                }
+            };
+            Some(Channel {
+                node: gc.target().node().index(),
+                property,
+                sampler: Sampler { times, values, width, interpolation },
+            })
+        }).collect();
+        let name = ga.name().map(str::to_string).unwrap_or_else(|| format!("animation {}", ga.index()));
+        Animation::new(name, channels)
+    }).collect()
+}
+
+/// Maps a glTF mode to a wgpu topology, rewriting the indices of modes wgpu can't draw directly.
+fn convert_primitive_mode(mode: gltf::mesh::Mode, indices: Vec<u32>) -> (wgpu::PrimitiveTopology, Vec<u32>) {
     match mode {
-        gltf::mesh::Mode::Points => wgpu::PrimitiveTopology::PointList,
-        gltf::mesh::Mode::Lines => wgpu::PrimitiveTopology::LineList,
-        gltf::mesh::Mode::LineStrip => wgpu::PrimitiveTopology::LineStrip,
-        gltf::mesh::Mode::Triangles => wgpu::PrimitiveTopology::TriangleList,
-        gltf::mesh::Mode::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
-        _ => panic!("Error loading mesh topology isn't supported!"),
+        gltf::mesh::Mode::Points => (wgpu::PrimitiveTopology::PointList, indices),
+        gltf::mesh::Mode::Lines => (wgpu::PrimitiveTopology::LineList, indices),
+        gltf::mesh::Mode::LineStrip => (wgpu::PrimitiveTopology::LineStrip, indices),
+        gltf::mesh::Mode::LineLoop => {
+            let mut indices = indices;
+            if let Some(&first) = indices.first() {
+                indices.push(first);
+            }
+            (wgpu::PrimitiveTopology::LineStrip, indices)
+        }
+        gltf::mesh::Mode::Triangles => (wgpu::PrimitiveTopology::TriangleList, indices),
+        gltf::mesh::Mode::TriangleStrip => (wgpu::PrimitiveTopology::TriangleStrip, indices),
+        gltf::mesh::Mode::TriangleFan => {
+            let fan = indices
+                .windows(2)
+                .skip(1)
+                .flat_map(|w| vec![indices[0], w[0], w[1]])
+                .collect();
+            (wgpu::PrimitiveTopology::TriangleList, fan)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris_are_percent_decoded() {
+        let base = std::path::Path::new("scenes");
+        assert_eq!(uri_path(base, "my%20texture.png"), base.join("my texture.png"));
+        assert_eq!(uri_path(base, "caf%C3%A9/a.png"), base.join("café/a.png"));
+        assert_eq!(uri_path(base, "file:///tmp/a%2Bb.bin"), std::path::PathBuf::from("/tmp/a+b.bin"));
+        // broken escapes stay as written
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%+1%4"), "%zz%+1%4");
+    }
+
+    #[test]
+    fn out_of_range_indices_fail_to_load() {
+        let result = load_meshes(&crate::golden::scene_path("bad_index"));
+        assert!(matches!(result, Err(Error::IndexOutOfRange { primitive: 0, index: 5, count: 3, .. })));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn line_loops_close_into_strips() {
+        let (mode, indices) = convert_primitive_mode(gltf::mesh::Mode::LineLoop, vec![0, 1, 2]);
+        assert_eq!(mode, wgpu::PrimitiveTopology::LineStrip);
+        assert_eq!(indices, vec![0, 1, 2, 0]);
+
+        assert_eq!(convert_primitive_mode(gltf::mesh::Mode::LineLoop, vec![]).1, Vec::<u32>::new());
+        assert_eq!(convert_primitive_mode(gltf::mesh::Mode::LineLoop, vec![4]).1, vec![4, 4]);
+    }
+
+    #[test]
+    fn triangle_fans_become_lists() {
+        let (mode, indices) = convert_primitive_mode(gltf::mesh::Mode::TriangleFan, vec![0, 1, 2, 3, 4]);
+        assert_eq!(mode, wgpu::PrimitiveTopology::TriangleList);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+
+        // fewer than three indices make no triangle
+        for short in &[vec![], vec![0], vec![0, 1]] {
+            let (mode, indices) = convert_primitive_mode(gltf::mesh::Mode::TriangleFan, short.clone());
+            assert_eq!(mode, wgpu::PrimitiveTopology::TriangleList);
+            assert!(indices.is_empty());
+        }
+    }
+}
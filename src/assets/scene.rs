@@ -0,0 +1,194 @@
+use super::{Animation, DefaultTextures, GpuMaterial, Material, Mesh, MorphedMesh, Skin, Texture};
+use crate::math;
+use std::sync::Arc;
+
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub translation: glam::Vec3,
+    pub rotation: glam::Quat,
+    pub scale: glam::Vec3,
+    /// Index into `Scene::meshes`.
+    pub mesh: Option<usize>,
+    /// Index into `Scene::skins`, deforms `mesh`.
+    pub skin: Option<usize>,
+    /// Morph target weights of `mesh`, its defaults when empty.
+    pub weights: Vec<f32>,
+    /// This node's copy of a morphed `mesh`, kept up to date by `Scene::update_morphs`.
+    pub morphed: Option<MorphedMesh>,
+    /// Computed by `Scene::update_transforms`.
+    pub world: glam::Mat4,
+}
+
+impl Node {
+    pub fn local(&self) -> glam::Mat4 {
+        glam::Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            parent: None,
+            children: Vec::new(),
+            translation: glam::Vec3::zero(),
+            rotation: glam::Quat::identity(),
+            scale: glam::Vec3::one(),
+            mesh: None,
+            skin: None,
+            weights: Vec::new(),
+            morphed: None,
+            world: glam::Mat4::identity(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub node: usize,
+    pub mesh: usize,
+    /// Index into `Mesh::subs`.
+    pub primitive: usize,
+    /// Index of the triangle within the primitive.
+    pub triangle: usize,
+    /// Weights of the triangle's vertices at the hit point.
+    pub barycentric: glam::Vec3,
+    /// World space distance along the ray, in multiples of its direction.
+    pub distance: f32,
+}
+
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+    pub nodes: Vec<Node>,
+    pub roots: Vec<usize>,
+    pub materials: Vec<Material>,
+    /// Parallel to `materials`.
+    pub gpu_materials: Vec<GpuMaterial>,
+    /// Shared with the `TextureCache` the scene was loaded with.
+    pub textures: Vec<Arc<Texture>>,
+    pub default_textures: DefaultTextures,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+    /// World space bounds of all mesh instances, computed by `update_transforms`.
+    pub aabb: math::Aabb,
+    pub sphere: math::Sphere,
+}
+
+impl Scene {
+    /// Reachable nodes, parents before their children.
+    pub fn traverse(&self) -> Vec<usize> {
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<usize> = self.roots.iter().rev().cloned().collect();
+        while let Some(index) = stack.pop() {
+            order.push(index);
+            stack.extend(self.nodes[index].children.iter().rev());
+        }
+        order
+    }
+
+    pub fn update_transforms(&mut self) {
+        for index in self.traverse() {
+            let parent = match self.nodes[index].parent {
+                Some(p) => self.nodes[p].world,
+                None => glam::Mat4::identity(),
+            };
+            let node = &mut self.nodes[index];
+            node.world = parent * node.local();
+        }
+
+        self.aabb = math::Aabb::empty();
+        self.sphere = math::Sphere::empty();
+        for (_, mesh, world) in self.mesh_instances() {
+            let mesh = &self.meshes[mesh];
+            self.aabb = self.aabb.union(mesh.aabb.transform(&world));
+            self.sphere = self.sphere.union(mesh.sphere.transform(&world));
+        }
+    }
+
+    /// Blends the morph targets of every mesh instance by the weights of its node, into
+    /// vertex buffers of the node's own.
+    pub fn update_morphs(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        for (node, mesh, _) in self.mesh_instances() {
+            let mesh = &self.meshes[mesh];
+            if mesh.weights.is_empty() {
+                continue;
+            }
+            let Node { weights, morphed, .. } = &mut self.nodes[node];
+            let weights = if weights.is_empty() { &mesh.weights } else { &*weights };
+            mesh.morph(device, queue, morphed.get_or_insert_with(MorphedMesh::default), weights);
+        }
+    }
+
+    /// Vertex buffer to draw `primitive` of `node`'s mesh from, the node's morphed copy if
+    /// it has one.
+    pub fn vertex_buffer(&self, node: usize, primitive: usize) -> Option<&wgpu::Buffer> {
+        let node = &self.nodes[node];
+        let morphed = node.morphed.as_ref().and_then(|morphed| morphed.vertex_buffers.get(primitive)?.as_ref());
+        morphed.or_else(|| Some(&self.meshes[node.mesh?].subs.get(primitive)?.gpu.as_ref()?.vertex_buffer))
+    }
+
+    /// Joint matrices of the skinned mesh of `node`.
+    pub fn joint_matrices(&self, node: usize) -> Option<Vec<glam::Mat4>> {
+        let node = &self.nodes[node];
+        node.mesh?;
+        Some(self.skins.get(node.skin?)?.joint_matrices(&self.nodes, node.world))
+    }
+
+    /// Nodes that reference a mesh as `(node, mesh, world)`, in draw order.
+    pub fn mesh_instances(&self) -> Vec<(usize, usize, glam::Mat4)> {
+        self.traverse()
+            .into_iter()
+            .filter_map(|index| {
+                let node = &self.nodes[index];
+                node.mesh.map(|mesh| (index, mesh, node.world))
+            })
+            .collect()
+    }
+
+    /// World space bounds of the mesh of `node`.
+    pub fn node_bounds(&self, node: usize) -> Option<(math::Aabb, math::Sphere)> {
+        let node = &self.nodes[node];
+        let mesh = &self.meshes[node.mesh?];
+        Some((mesh.aabb.transform(&node.world), mesh.sphere.transform(&node.world)))
+    }
+
+    /// Closest mesh triangle hit by a world space ray.
+    pub fn raycast(&self, ray: &math::Ray) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        for (node, mesh, world) in self.mesh_instances() {
+            // only singular transforms, tiny scales such as mm to m still invert fine
+            let inverse = world.inverse();
+            if !inverse.to_cols_array().iter().all(|v| v.is_finite()) {
+                continue;
+            }
+            let limit = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            let local = ray.transform(&inverse);
+            if let Some(hit) = self.meshes[mesh].bvh.raycast(&local, limit) {
+                closest = Some(RayHit {
+                    node,
+                    mesh,
+                    primitive: hit.primitive,
+                    triangle: hit.triangle,
+                    barycentric: hit.barycentric,
+                    distance: hit.distance,
+                });
+            }
+        }
+        closest
+    }
+
+    /// Re-uploads every mesh that still has CPU data, e.g. after editing it.
+    pub fn upload_meshes(&mut self, device: &wgpu::Device) {
+        for mesh in &mut self.meshes {
+            mesh.upload(device);
+        }
+    }
+
+    pub fn release_mesh_data(&mut self) {
+        for mesh in &mut self.meshes {
+            mesh.release();
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::{Error, Mesh, Node, Result};
+use crate::pipeline::MAX_JOINTS;
+
+pub struct Skin {
+    pub name: String,
+    /// Indices into `Scene::nodes`.
+    pub joints: Vec<usize>,
+    /// One per joint, identity when the file has none.
+    pub inverse_bind: Vec<glam::Mat4>,
+    /// Root of the joint hierarchy, informational only.
+    pub skeleton: Option<usize>,
+}
+
+impl Skin {
+    /// Matrices that move bind pose vertices of a mesh instanced at `world` to the
+    /// current pose, in the mesh's own space so the model matrix still applies.
+    pub fn joint_matrices(&self, nodes: &[Node], world: glam::Mat4) -> Vec<glam::Mat4> {
+        let inverse = world.inverse();
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind)
+            .map(|(joint, inverse_bind)| inverse * nodes[*joint].world * *inverse_bind)
+            .collect()
+    }
+
+    /// Rejects skinning `mesh` with more joints than the shaders hold, or with vertices
+    /// that reference joints the skin doesn't have. Needs the mesh's CPU data.
+    pub fn check(&self, mesh: &Mesh) -> Result<()> {
+        if self.joints.len() > MAX_JOINTS {
+            return Err(Error::TooManyJoints { skin: self.name.clone(), joints: self.joints.len(), max: MAX_JOINTS });
+        }
+        let joints = mesh.subs.iter().filter_map(|sub| sub.data.as_ref()).flat_map(|data| data.joints.iter().flatten());
+        match joints.map(|joint| *joint as usize).find(|joint| *joint >= self.joints.len()) {
+            Some(joint) => Err(Error::JointOutOfRange { mesh: mesh.name.clone(), joint, joints: self.joints.len() }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bind_pose_is_identity() {
+        let joint = Node {
+            world: glam::Mat4::from_translation(glam::vec3(0.0, 2.0, 0.0)),
+            ..Node::default()
+        };
+        let world = glam::Mat4::from_translation(glam::vec3(5.0, 0.0, 0.0));
+        let skin = Skin {
+            name: "skin".to_string(),
+            joints: vec![0],
+            // the joint in the mesh's space
+            inverse_bind: vec![glam::Mat4::from_translation(glam::vec3(5.0, -2.0, 0.0))],
+            skeleton: None,
+        };
+        let matrices = skin.joint_matrices(&[joint], world);
+        assert!(matrices[0].abs_diff_eq(glam::Mat4::identity(), 1e-6));
+    }
+
+    #[test]
+    fn oversized_skins_are_rejected() {
+        let meshes = crate::assets::load_meshes(&crate::golden::scene_path("skinned")).unwrap();
+        let mut skin = Skin {
+            name: "skin".to_string(),
+            joints: vec![0, 1],
+            inverse_bind: vec![glam::Mat4::identity(); 2],
+            skeleton: None,
+        };
+        assert!(skin.check(&meshes[0]).is_ok());
+
+        skin.joints.truncate(1);
+        assert!(matches!(skin.check(&meshes[0]), Err(Error::JointOutOfRange { joint: 1, joints: 1, .. })));
+
+        skin.joints = vec![0; MAX_JOINTS + 1];
+        assert!(matches!(skin.check(&meshes[0]), Err(Error::TooManyJoints { .. })));
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::Result;
+
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+    pub width: u32,
+    pub height: u32,
+    pub mip_level_count: u32,
+}
+
+impl Texture {
+    /// Uploads RGBA8 pixels, with a full mip chain when `mipmaps` is set.
+    pub fn from_rgba(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        (width, height): (u32, u32),
+        pixels: &[u8],
+        format: wgpu::TextureFormat,
+        sampler: &wgpu::SamplerDescriptor,
+        mipmaps: bool,
+    ) -> Self {
+        let mip_level_count = if mipmaps {
+            32 - width.max(height).leading_zeros()
+        } else {
+            1
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("texture"),
+            size: wgpu::Extent3d { width, height, depth: 1 },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+
+        let srgb = format == wgpu::TextureFormat::Rgba8UnormSrgb;
+        let mut level = (width, height, pixels.to_vec());
+        for mip_level in 0..mip_level_count {
+            let (w, h, ref data) = level;
+            queue.write_texture(
+                wgpu::TextureCopyView {
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: 4 * w,
+                    rows_per_image: h,
+                },
+                wgpu::Extent3d { width: w, height: h, depth: 1 },
+            );
+            if mip_level + 1 < mip_level_count {
+                level = downsample(w, h, data, srgb);
+            }
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(sampler);
+        Texture { texture, view, sampler, width, height, mip_level_count }
+    }
+
+    /// 1x1 texture used in place of a missing material map.
+    pub fn solid(device: &wgpu::Device, queue: &wgpu::Queue, color: [u8; 4]) -> Self {
+        Texture::from_rgba(
+            device,
+            queue,
+            (1, 1),
+            &color,
+            wgpu::TextureFormat::Rgba8Unorm,
+            &wgpu::SamplerDescriptor::default(),
+            false,
+        )
+    }
+
+    pub fn from_encoded(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        sampler: &wgpu::SamplerDescriptor,
+        srgb: bool,
+    ) -> Result<Self> {
+        let image = image::load_from_memory(bytes)?.into_rgba8();
+        let format = if srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        Ok(Texture::from_rgba(device, queue, image.dimensions(), &image, format, sampler, true))
+    }
+}
+
+fn srgb_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> u8 {
+    let c = if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 };
+    (c * 255.0).round().max(0.0).min(255.0) as u8
+}
+
+/// 2x2 box filter, averaging color in linear space for sRGB data.
+fn downsample(width: u32, height: u32, pixels: &[u8], srgb: bool) -> (u32, u32, Vec<u8>) {
+    let (w, h) = ((width / 2).max(1), (height / 2).max(1));
+    let mut out = Vec::with_capacity((w * h * 4) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = [0.0f32; 4];
+            for (dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let sx = (x * 2 + dx).min(width - 1);
+                let sy = (y * 2 + dy).min(height - 1);
+                let p = &pixels[((sy * width + sx) * 4) as usize..][..4];
+                for c in 0..4 {
+                    sum[c] += if srgb && c < 3 { srgb_to_linear(p[c]) } else { p[c] as f32 / 255.0 };
+                }
+            }
+            for (c, s) in sum.iter().enumerate() {
+                let v = s / 4.0;
+                out.push(if srgb && c < 3 { linear_to_srgb(v) } else { (v * 255.0).round() as u8 });
+            }
+        }
+    }
+    (w, h, out)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum TextureSource {
+    File(PathBuf),
+    /// Hash of a data URI or of the bytes of an embedded buffer view.
+    Embedded(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TextureKey {
+    source: TextureSource,
+    srgb: bool,
+    address_mode: (wgpu::AddressMode, wgpu::AddressMode),
+    filter: (wgpu::FilterMode, wgpu::FilterMode, wgpu::FilterMode),
+}
+
+/// Shares decoded and uploaded images between materials and scenes.
+#[derive(Default)]
+pub struct TextureCache {
+    entries: HashMap<TextureKey, Arc<Texture>>,
+}
+
+fn hash_of<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the image of `gt`, or returns the texture already uploaded from the same source.
+    pub fn load(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        gt: &gltf::texture::Texture,
+        base: &Path,
+        buffers: &[gltf::buffer::Data],
+        srgb: bool,
+    ) -> Result<Arc<Texture>> {
+        let view_bytes = |view: gltf::buffer::View| -> &[u8] {
+            &buffers[view.buffer().index()][view.offset()..view.offset() + view.length()]
+        };
+        let source = match gt.source().source() {
+            gltf::image::Source::View { view, .. } => TextureSource::Embedded(hash_of(view_bytes(view))),
+            gltf::image::Source::Uri { uri, .. } if uri.starts_with("data:") => TextureSource::Embedded(hash_of(uri)),
+            gltf::image::Source::Uri { uri, .. } => {
+                let path = super::uri_path(base, uri);
+                TextureSource::File(path.canonicalize().unwrap_or(path))
+            }
+        };
+
+        let sampler = sampler_descriptor(&gt.sampler());
+        let key = TextureKey {
+            source,
+            srgb,
+            address_mode: (sampler.address_mode_u, sampler.address_mode_v),
+            filter: (sampler.mag_filter, sampler.min_filter, sampler.mipmap_filter),
+        };
+        if let Some(texture) = self.entries.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let bytes = match gt.source().source() {
+            gltf::image::Source::View { view, .. } => view_bytes(view).to_vec(),
+            gltf::image::Source::Uri { uri, .. } => super::read_uri(base, uri)?,
+        };
+        let texture = Arc::new(Texture::from_encoded(device, queue, &bytes, &sampler, srgb)?);
+        self.entries.insert(key, texture.clone());
+        Ok(texture)
+    }
+}
+
+fn sampler_descriptor(sampler: &gltf::texture::Sampler) -> wgpu::SamplerDescriptor<'static> {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        _ => wgpu::FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest),
+        Some(MinFilter::NearestMipmapLinear) => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear),
+        Some(MinFilter::LinearMipmapNearest) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest),
+        _ => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear),
+    };
+
+    wgpu::SamplerDescriptor {
+        label: Some("texture sampler"),
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        ..Default::default()
+    }
+}
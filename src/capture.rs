@@ -0,0 +1,142 @@
+use std::io::Write;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// Tightly packed RGBA8 pixels read back from a color target.
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+pub fn create_target(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("capture target"),
+        size: wgpu::Extent3d {
+            width: sc_desc.width,
+            height: sc_desc.height,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: sc_desc.format,
+        usage: sc_desc.usage | wgpu::TextureUsage::COPY_SRC,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+) -> Result<Frame> {
+    let swizzle = match format {
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        _ => return Err(format!("can't capture texture format {:?}", format).into()),
+    };
+
+    let unpadded = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded = (unpadded + align - 1) / align * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("capture readback"),
+        size: (padded * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_texture_to_buffer(
+        wgpu::TextureCopyView {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::BufferCopyView {
+            buffer: &buffer,
+            layout: wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: padded,
+                rows_per_image: height,
+            },
+        },
+        wgpu::Extent3d { width, height, depth: 1 },
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    device.poll(wgpu::Maintain::Wait);
+    futures::executor::block_on(mapping)?;
+
+    let mut pixels = Vec::with_capacity((unpadded * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks(padded as usize) {
+            pixels.extend_from_slice(&row[..unpadded as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if swizzle {
+        for pixel in pixels.chunks_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    Ok(Frame { width, height, pixels })
+}
+
+impl Frame {
+    pub fn load_png(path: &std::path::Path) -> Result<Frame> {
+        let decoder = png::Decoder::new(std::fs::File::open(path)?);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)?;
+
+        let pixels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::RGBA, png::BitDepth::Eight) => data,
+            (png::ColorType::RGB, png::BitDepth::Eight) => data
+                .chunks(3)
+                .flat_map(|p| vec![p[0], p[1], p[2], 255])
+                .collect(),
+            other => return Err(format!("unsupported png layout {:?}", other).into()),
+        };
+
+        Ok(Frame { width: info.width, height: info.height, pixels })
+    }
+
+    pub fn save(&self, path: &std::path::Path) -> Result<()> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ppm") => self.save_ppm(path),
+            _ => self.save_png(path),
+        }
+    }
+
+    pub fn save_png(&self, path: &std::path::Path) -> Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    /// Binary PPM drops the alpha channel.
+    pub fn save_ppm(&self, path: &std::path::Path) -> Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        write!(file, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.pixels.chunks(4) {
+            file.write_all(&pixel[..3])?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Golden-image comparison for offscreen renders.
+//!
+//! References live in `tests/golden/<name>.png`. Set `UG_BLESS=1` to write them
+//! from the current render, for new tests or after an intended visual change. On
+//! mismatch the actual image and a diff image are written to `target/golden/`.
+//!
+//! Tests that render need a GPU adapter and only run with the `gpu-tests` feature.
+
+use crate::capture::Frame;
+use crate::{app, Example};
+use std::path::PathBuf;
+
+pub struct Tolerance {
+    /// Largest per-channel difference that still counts as equal.
+    pub channel: u8,
+    /// Largest normalized YIQ color distance (0..1) that still counts as equal.
+    pub perceptual: f32,
+    /// Fraction of pixels allowed to differ.
+    pub mismatch: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            perceptual: 0.01,
+            mismatch: 0.001,
+        }
+    }
+}
+
+pub struct Comparison {
+    pub mismatched: usize,
+    pub max_channel_delta: u8,
+    pub mean_perceptual_delta: f32,
+    pub diff: Frame,
+}
+
+// Weighted YIQ distance, as used by pixelmatch.
+fn perceptual_delta(a: &[u8], b: &[u8]) -> f32 {
+    let yiq = |p: &[u8]| {
+        let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+        (
+            r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_23,
+            r * 0.595_977_99 - g * 0.274_176_1 - b * 0.321_801_89,
+            r * 0.211_470_17 - g * 0.522_617_1 + b * 0.311_146_94,
+        )
+    };
+    let (ya, ia, qa) = yiq(a);
+    let (yb, ib, qb) = yiq(b);
+    let delta = 0.5053 * (ya - yb).powi(2) + 0.299 * (ia - ib).powi(2) + 0.1957 * (qa - qb).powi(2);
+    delta / 35215.0
+}
+
+pub fn compare(actual: &Frame, expected: &Frame, tolerance: &Tolerance) -> Comparison {
+    assert_eq!(
+        (actual.width, actual.height),
+        (expected.width, expected.height),
+        "golden image size mismatch"
+    );
+
+    let mut mismatched = 0;
+    let mut max_channel_delta = 0;
+    let mut perceptual_sum = 0.0;
+    let mut diff = Vec::with_capacity(actual.pixels.len());
+
+    for (a, e) in actual.pixels.chunks(4).zip(expected.pixels.chunks(4)) {
+        let channel = a.iter().zip(e).map(|(a, e)| (*a as i16 - *e as i16).abs() as u8).max().unwrap_or(0);
+        let perceptual = perceptual_delta(a, e);
+        max_channel_delta = max_channel_delta.max(channel);
+        perceptual_sum += perceptual;
+
+        if channel > tolerance.channel && perceptual > tolerance.perceptual {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            // faded grayscale of the reference so the red stands out
+            let luma = (e[0] as u32 * 3 + e[1] as u32 * 6 + e[2] as u32) / 10;
+            let faded = (255 - (255 - luma) / 4) as u8;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    let count = (actual.width * actual.height).max(1) as f32;
+    Comparison {
+        mismatched,
+        max_channel_delta,
+        mean_perceptual_delta: perceptual_sum / count,
+        diff: Frame {
+            width: actual.width,
+            height: actual.height,
+            pixels: diff,
+        },
+    }
+}
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+pub fn reference_path(name: &str) -> PathBuf {
+    root().join("tests").join("golden").join(format!("{}.png", name))
+}
+
+pub fn scene_path(name: &str) -> PathBuf {
+    root().join("tests").join("scenes").join(format!("{}.gltf", name))
+}
+
+/// Headless `Example` for tests marked `#[cfg_attr(not(feature = "gpu-tests"), ignore)]`.
+pub fn headless(width: u32, height: u32) -> app::Headless<Example> {
+    app::run_headless::<Example>(width, height).expect("no GPU adapter available, run without the gpu-tests feature to skip GPU tests!")
+}
+
+/// Compares `actual` with the reference `name`, panicking with a report on mismatch.
+pub fn check(name: &str, actual: &Frame, tolerance: &Tolerance) {
+    let reference = reference_path(name);
+    if std::env::var_os("UG_BLESS").is_some() {
+        std::fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        actual.save_png(&reference).unwrap();
+        println!("blessed golden image {}", reference.display());
+        return;
+    }
+    if !reference.exists() {
+        panic!(
+            "golden image {} is missing, render it with `UG_BLESS=1 cargo test --features gpu-tests`, check it and commit it",
+            reference.display(),
+        );
+    }
+
+    let expected = Frame::load_png(&reference).unwrap();
+    let result = compare(actual, &expected, tolerance);
+    let allowed = (tolerance.mismatch * (actual.width * actual.height) as f32) as usize;
+    if result.mismatched <= allowed {
+        return;
+    }
+
+    let out = root().join("target").join("golden");
+    std::fs::create_dir_all(&out).unwrap();
+    let actual_path = out.join(format!("{}.actual.png", name));
+    let diff_path = out.join(format!("{}.diff.png", name));
+    actual.save_png(&actual_path).unwrap();
+    result.diff.save_png(&diff_path).unwrap();
+
+    panic!(
+        "golden image {} differs: {} pixels mismatched (allowed {}), max channel delta {}, mean perceptual delta {:.5}\n  actual: {}\n  diff: {}",
+        name,
+        result.mismatched,
+        allowed,
+        result.max_channel_delta,
+        result.mean_perceptual_delta,
+        actual_path.display(),
+        diff_path.display(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assets, Shading};
+
+    fn render_scene(name: &str, shading: Shading) -> Frame {
+        let mut headless = headless(256, 256);
+        let scene = futures::executor::block_on(assets::from_gltf(headless.device(), headless.queue(), &scene_path(name))).unwrap();
+        headless.app().scene = Some(scene);
+        headless.app().shading = shading;
+        headless.render();
+        headless.read_frame()
+    }
+
+    fn golden(name: &str, shading: Shading) {
+        let frame = render_scene(name, shading);
+        let reference = match shading {
+            Shading::Flat => name.to_string(),
+            Shading::Pbr => format!("{}.pbr", name),
+        };
+        check(&reference, &frame, &Tolerance::default());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn triangle() {
+        golden("triangle", Shading::Flat);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn cube() {
+        golden("cube", Shading::Flat);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn cube_pbr() {
+        golden("cube", Shading::Pbr);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn textured_pbr() {
+        golden("textured", Shading::Pbr);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn pick_cube() {
+        let mut headless = headless(256, 256);
+        let scene = futures::executor::block_on(assets::from_gltf(headless.device(), headless.queue(), &scene_path("cube"))).unwrap();
+        headless.app().scene = Some(scene);
+
+        headless.click(128.0, 128.0);
+        headless.render();
+        let pick = headless.app().selection.expect("cube wasn't picked");
+        assert_eq!((pick.mesh, pick.primitive), (0, 0));
+        assert!(headless.app().selection_point.is_some());
+
+        headless.click(0.0, 0.0);
+        headless.render();
+        assert_eq!(headless.app().selection, None);
+        assert_eq!(headless.app().selection_point, None);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn skinned_pbr() {
+        let mut headless = headless(256, 256);
+        let scene = futures::executor::block_on(assets::from_gltf(headless.device(), headless.queue(), &scene_path("skinned"))).unwrap();
+        assert_eq!((scene.skins.len(), scene.animations.len()), (1, 1));
+        headless.app().scene = Some(scene);
+        // hold the pose at the peak of the bend
+        headless.app().player = Some(assets::AnimationPlayer::new(0));
+        headless.app().timeline.seek(1.0);
+        headless.app().timeline.pause();
+        headless.render();
+        check("skinned.pbr", &headless.read_frame(), &Tolerance::default());
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn morph_pbr() {
+        let mut headless = headless(256, 256);
+        let scene = futures::executor::block_on(assets::from_gltf(headless.device(), headless.queue(), &scene_path("morph"))).unwrap();
+        assert!(scene.meshes[0].subs[0].data.is_some(), "morphed submeshes keep their CPU copy");
+        headless.app().scene = Some(scene);
+        headless.app().player = Some(assets::AnimationPlayer::new(0));
+        headless.render();
+        // fully stretched a second later, overriding the mesh's default weight
+        headless.advance(std::time::Duration::from_secs(1));
+        headless.render();
+        let node = &headless.app().scene.as_ref().unwrap().nodes[0];
+        assert_eq!(node.weights, vec![1.0]);
+        assert!(node.morphed.is_some(), "morphed instances draw from their own vertex buffers");
+        check("morph.pbr", &headless.read_frame(), &Tolerance::default());
+    }
+
+    #[test]
+    fn identical_frames_match() {
+        let frame = Frame {
+            width: 2,
+            height: 1,
+            pixels: vec![10, 20, 30, 255, 200, 100, 0, 255],
+        };
+        let result = compare(&frame, &frame, &Tolerance::default());
+        assert_eq!(result.mismatched, 0);
+        assert_eq!(result.max_channel_delta, 0);
+    }
+
+    #[test]
+    fn different_frames_mismatch() {
+        let black = Frame { width: 1, height: 1, pixels: vec![0, 0, 0, 255] };
+        let white = Frame { width: 1, height: 1, pixels: vec![255, 255, 255, 255] };
+        let result = compare(&black, &white, &Tolerance::default());
+        assert_eq!(result.mismatched, 1);
+        assert_eq!(result.max_channel_delta, 255);
+        assert_eq!(&result.diff.pixels, &[255, 0, 0, 255]);
+    }
+}
@@ -1,46 +1,116 @@
 use winit::{
-    event::WindowEvent,
+    event::{self, WindowEvent},
 };
 
 
+mod capture;
 mod shader;
 mod pipeline;
 mod app;
 mod assets;
 mod math;
+mod time;
+#[cfg(test)]
+mod golden;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shading {
+    Flat,
+    Pbr,
+}
+
+/// Distance of the orbit target in front of the eye when switching from fly to orbit navigation.
+const ORBIT_DISTANCE: f32 = 5.0;
+const FOV: f32 = 45.0;
 
 struct Example {
     scene: Option<assets::Scene>,
     camera: math::Camera,
+    controller: Box<dyn math::CameraController>,
+    orbiting: bool,
     uniform_buffer: wgpu::Buffer,
-    _color_buffer: wgpu::Buffer,
+    color_buffer: wgpu::Buffer,
+    model_buffer: pipeline::ModelBuffer,
     pipeline: pipeline::PipelineResource,
-    bind_group: wgpu::BindGroup
+    bind_group: wgpu::BindGroup,
+    pbr_pipeline: pipeline::PipelineResource,
+    pbr_bind_group: wgpu::BindGroup,
+    joint_buffer: pipeline::JointBuffer,
+    skinned_pipeline: pipeline::PipelineResource,
+    skinned_bind_group: wgpu::BindGroup,
+    skinned_select_pipeline: pipeline::PipelineResource,
+    skinned_select_bind_group: wgpu::BindGroup,
+    /// Plays the scene's first animation, following `timeline`.
+    player: Option<assets::AnimationPlayer>,
+    timeline: time::Timeline,
+    shading: Shading,
+    depth: pipeline::DepthTexture,
+    picker: pipeline::Picker,
+    /// Cursor position of a click to resolve on the next frame.
+    pending_pick: Option<(f32, f32)>,
+    selection: Option<pipeline::Pick>,
+    /// World space point clicked on the selection.
+    selection_point: Option<glam::Vec3>,
+    size: (u32, u32),
+    /// Watches the shader sources in debug builds.
+    shader_watcher: Option<shader::ShaderWatcher>,
+    /// Found by `needs_redraw`, reloaded by the next `render`.
+    changed_shaders: Vec<std::path::PathBuf>,
 }
 
 impl app::App for Example {
+    fn run_config() -> app::RunConfig {
+        app::RunConfig {
+            redraw: app::Redraw::OnDemand,
+            ..app::RunConfig::default()
+        }
+    }
+
+    fn needs_redraw(&mut self) -> bool {
+        if let Some(watcher) = &mut self.shader_watcher {
+            self.changed_shaders.extend(watcher.poll());
+        }
+        let animating = self.player.is_some() && self.timeline.is_playing();
+        animating || !self.changed_shaders.is_empty() || self.controller.is_moving() || self.pending_pick.is_some()
+    }
+
+    fn next_check(&self) -> Option<std::time::Instant> {
+        self.shader_watcher.as_ref().map(shader::ShaderWatcher::next_poll)
+    }
+
     fn init(device: &wgpu::Device, queue: &wgpu::Queue, sc_desc: &wgpu::SwapChainDescriptor) -> Self {
         let scene = {
             if let Some(path) = std::env::args().nth(1) {
                 let path = std::path::Path::new(&path);
                 if path.is_file() {
-                    Some(futures::executor::block_on(assets::from_gltf(device, &path)))
+                    match futures::executor::block_on(assets::from_gltf(device, queue, &path)) {
+                        Ok(scene) => Some(scene),
+                        Err(e) => {
+                            eprintln!("failed to load {}: {}", path.display(), e);
+                            None
+                        }
+                    }
                 } else {None}
             } else {None}
         };
 
-        let pipeline = pipeline::create_select_pipeline(device, sc_desc.format).unwrap();
+        let depth = pipeline::DepthTexture::new(device, sc_desc, pipeline::DepthConfig::reversed());
+        let pipeline = pipeline::create_select_pipeline(device, sc_desc.format, Some(depth.config)).unwrap();
+        let pbr_pipeline = pipeline::create_pbr_pipeline(device, sc_desc.format, Some(depth.config)).unwrap();
+        let skinned_pipeline = pipeline::create_skinned_pipeline(device, sc_desc.format, Some(depth.config)).unwrap();
+        let skinned_select_pipeline = pipeline::create_skinned_select_pipeline(device, sc_desc.format, Some(depth.config)).unwrap();
 
         let camera = {
             let aspect = sc_desc.width as f32 / sc_desc.height as f32;
-            let camera = math::perspective(aspect, 45.0, 1.0, 1000.0);
+            let camera = math::perspective_reversed(aspect, FOV, 1.0, 1000.0);
             camera
         };
+        let controller = Box::new(math::OrbitController::looking_at(glam::vec3(3.0, 3.0, 3.0), glam::Vec3::zero()));
 
         let uniform_buffer = {
             device.create_buffer(&wgpu::BufferDescriptor{
                 label: Some("camera uniform"),
-                size: std::mem::size_of::<math::Camera>() as _,
+                size: std::mem::size_of::<math::CameraUniform>() as _,
                 usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
                 mapped_at_creation: false,
             })
@@ -59,78 +129,467 @@ impl app::App for Example {
             queue.write_buffer(&color_buffer, 0, bytemuck::cast_slice(&[color]));
         }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &pipeline.bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Buffer(color_buffer.slice(..)),
-                }
-            ],
-            label: None,
-        });
+        let model_buffer = pipeline::ModelBuffer::new(device, 1);
+
+        let bind_group = create_bind_group(device, &pipeline, &uniform_buffer, &color_buffer, &model_buffer);
+        let pbr_bind_group = create_pbr_bind_group(device, &pbr_pipeline, &uniform_buffer, &model_buffer);
+        let joint_buffer = pipeline::JointBuffer::new(device, 1);
+        let skinned_bind_group = create_skinned_bind_group(device, &skinned_pipeline, &uniform_buffer, &model_buffer, &joint_buffer);
+        let skinned_select_bind_group = create_skinned_select_bind_group(
+            device, &skinned_select_pipeline, &uniform_buffer, &color_buffer, &model_buffer, &joint_buffer,
+        );
+        let player = scene.as_ref().filter(|s| !s.animations.is_empty()).map(|_| assets::AnimationPlayer::new(0));
+        let timeline = time::Timeline::new(scene.as_ref().and_then(|s| s.animations.first()).map(|a| a.duration));
+        let mut picker = pipeline::Picker::new(device, sc_desc, depth.config).unwrap();
+        picker.bind(device, &uniform_buffer, &model_buffer, &joint_buffer);
 
-        Example {
+        let shader_watcher = if cfg!(debug_assertions) {
+            let paths = pipeline.shader_paths()
+                .into_iter()
+                .chain(pbr_pipeline.shader_paths())
+                .chain(skinned_pipeline.shader_paths())
+                .chain(skinned_select_pipeline.shader_paths())
+                .chain(picker.shader_paths());
+            Some(shader::ShaderWatcher::new(paths))
+        } else {
+            None
+        };
+
+        let mut example = Example {
             scene,
             camera,
+            controller,
+            orbiting: true,
             uniform_buffer,
-            _color_buffer: color_buffer,
+            color_buffer,
+            model_buffer,
             pipeline,
-            bind_group
+            bind_group,
+            pbr_pipeline,
+            pbr_bind_group,
+            joint_buffer,
+            skinned_pipeline,
+            skinned_bind_group,
+            skinned_select_pipeline,
+            skinned_select_bind_group,
+            player,
+            timeline,
+            shading: Shading::Pbr,
+            depth,
+            picker,
+            pending_pick: None,
+            selection: None,
+            selection_point: None,
+            size: (sc_desc.width, sc_desc.height),
+            shader_watcher,
+            changed_shaders: Vec::new(),
+        };
+        if let Some(sphere) = example.scene.as_ref().map(|s| s.sphere) {
+            example.frame(sphere);
         }
+        example
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, _queue: &wgpu::Queue, sc_desc: &wgpu::SwapChainDescriptor) {
+        self.depth.resize(device, sc_desc);
+        self.picker.resize(device, sc_desc);
+        self.size = (sc_desc.width, sc_desc.height);
     }
 
-    fn update(&mut self, _event: WindowEvent) {
-        
+    fn click(&mut self, x: f32, y: f32) {
+        self.pending_pick = Some((x, y));
     }
 
-    fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: &wgpu::SwapChainTexture, _spawner: &impl futures::task::LocalSpawn) {
-        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.camera]));
+    fn update(&mut self, event: WindowEvent) {
+        self.controller.window_event(&event);
+
+        if let WindowEvent::KeyboardInput {
+            input:
+                event::KeyboardInput {
+                    virtual_keycode: Some(key),
+                    state: event::ElementState::Pressed,
+                    ..
+                },
+            ..
+        } = event
+        {
+            match key {
+                event::VirtualKeyCode::Tab => {
+                    self.shading = match self.shading {
+                        Shading::Flat => Shading::Pbr,
+                        Shading::Pbr => Shading::Flat,
+                    };
+                }
+                event::VirtualKeyCode::Space => {
+                    if self.timeline.is_playing() {
+                        self.timeline.pause();
+                    } else {
+                        self.timeline.play();
+                    }
+                }
+                // switch between orbit and fly navigation, keeping the current view
+                event::VirtualKeyCode::C => {
+                    let (eye, forward) = math::view_frame(&self.camera.view());
+                    let target = eye + forward * ORBIT_DISTANCE;
+                    self.orbiting = !self.orbiting;
+                    self.controller = if self.orbiting {
+                        Box::new(math::OrbitController::looking_at(eye, target))
+                    } else {
+                        Box::new(math::FlyController::looking_at(eye, target))
+                    };
+                }
+                // frame the selection, or everything when nothing is selected
+                event::VirtualKeyCode::F => {
+                    let selection = self.selection;
+                    let sphere = self.scene.as_ref().map(|s| {
+                        selection
+                            .and_then(|pick| s.node_bounds(pick.node))
+                            .map(|(_, sphere)| sphere)
+                            .unwrap_or(s.sphere)
+                    });
+                    if let Some(sphere) = sphere {
+                        self.frame(sphere);
+                    }
+                }
+                // toggle an orthographic projection for CAD-style views
+                event::VirtualKeyCode::O => {
+                    self.camera.projection = match self.camera.projection {
+                        math::Projection::Perspective { far, .. } => math::Projection::Orthographic {
+                            height: 2.0 * ORBIT_DISTANCE * (FOV / 2.0).to_radians().tan(),
+                            // keep geometry between the eye and the orbit target visible
+                            near: -far,
+                            far,
+                        },
+                        math::Projection::Orthographic { far, .. } => math::Projection::Perspective { fov: FOV, near: (far * 1e-5).max(1e-4), far },
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn camera(&mut self) -> Option<&mut math::Camera> {
+        Some(&mut self.camera)
+    }
+
+    fn tick(&mut self, time: &time::FrameTime) {
+        self.controller.update(time.dt);
+        self.timeline.advance(time.dt);
+        if let (Some(player), Some(s)) = (&mut self.player, &mut self.scene) {
+            player.seek(s, self.timeline.time());
+        }
+    }
+
+    fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, frame: &wgpu::TextureView, _spawner: &impl futures::task::LocalSpawn) {
+        let changed = std::mem::take(&mut self.changed_shaders);
+        if !changed.is_empty() {
+            self.pipeline.reload(device, &changed);
+            self.pbr_pipeline.reload(device, &changed);
+            self.skinned_pipeline.reload(device, &changed);
+            self.skinned_select_pipeline.reload(device, &changed);
+            self.picker.reload(device, &changed);
+        }
+
+        if let Some(s) = &mut self.scene {
+            s.update_morphs(device, queue);
+        }
+        self.camera.set_view(self.controller.view());
+        if let math::Projection::Orthographic { ref mut height, .. } = self.camera.projection {
+            // match the perspective framing at the focus distance, so zooming still works
+            if let Some(distance) = self.controller.focus_distance() {
+                *height = 2.0 * distance * (FOV / 2.0).to_radians().tan();
+            }
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.camera.uniform()]));
+
+        let instances = self.scene.as_ref().map(|s| s.mesh_instances()).unwrap_or_default();
+        let models: Vec<glam::Mat4> = instances.iter().map(|(_, _, model)| *model).collect();
+        let models_grown = self.model_buffer.write(device, queue, &models);
+        if models_grown {
+            self.bind_group = create_bind_group(device, &self.pipeline, &self.uniform_buffer, &self.color_buffer, &self.model_buffer);
+            self.pbr_bind_group = create_pbr_bind_group(device, &self.pbr_pipeline, &self.uniform_buffer, &self.model_buffer);
+        }
+
+        // one joint palette per skinned instance
+        let mut palettes = Vec::new();
+        let joints: Vec<Option<usize>> = instances.iter().map(|(node, _, _)| {
+            let palette = self.scene.as_ref()?.joint_matrices(*node)?;
+            palettes.push(palette);
+            Some(palettes.len() - 1)
+        }).collect();
+        if self.joint_buffer.write(device, queue, &palettes) || models_grown {
+            self.skinned_bind_group = create_skinned_bind_group(
+                device, &self.skinned_pipeline, &self.uniform_buffer, &self.model_buffer, &self.joint_buffer,
+            );
+            self.skinned_select_bind_group = create_skinned_select_bind_group(
+                device, &self.skinned_select_pipeline, &self.uniform_buffer, &self.color_buffer, &self.model_buffer, &self.joint_buffer,
+            );
+            self.picker.bind(device, &self.uniform_buffer, &self.model_buffer, &self.joint_buffer);
+        }
+
+        // opaque draws first, then blended ones back to front so they composite over them
+        let mut draws = Vec::new();
+        if let Some(s) = &self.scene {
+            for (instance, (node, mesh, _)) in instances.iter().enumerate() {
+                for (primitive, sub) in s.meshes[*mesh].subs.iter().enumerate() {
+                    let (gpu, vertex_buffer) = match (&sub.gpu, s.vertex_buffer(*node, primitive)) {
+                        (Some(gpu), Some(vertex_buffer)) => (gpu, vertex_buffer),
+                        _ => continue,
+                    };
+                    draws.push(pipeline::Draw {
+                        instance,
+                        joints: joints[instance].filter(|_| gpu.skin_buffer.is_some()),
+                        pick: pipeline::Pick { node: *node, mesh: *mesh, primitive },
+                        sub,
+                        gpu,
+                        vertex_buffer,
+                        key: pipeline::PipelineKey::new(sub, &s.materials[sub.material]),
+                    });
+                }
+            }
+        }
+        let (eye, forward) = math::view_frame(&self.camera.view());
+        let depth = |draw: &pipeline::Draw| (models[draw.instance].transform_point3(draw.sub.sphere.center) - eye).dot(forward);
+        draws.sort_by(|a, b| match (a.key.blend, b.key.blend) {
+            (true, true) => depth(b).partial_cmp(&depth(a)).unwrap_or(std::cmp::Ordering::Equal),
+            _ => a.key.blend.cmp(&b.key.blend),
+        });
+
+        if let Some((x, y)) = self.pending_pick.take() {
+            self.selection = self.picker.pick(device, queue, &self.depth, &draws, (x as u32, y as u32));
+            self.selection_point = match (self.selection, &self.scene) {
+                (Some(_), Some(s)) => {
+                    // the exact surface point comes from the CPU geometry
+                    let ray = self.camera.ray(x, y, self.size.0, self.size.1);
+                    s.raycast(&ray).map(|hit| ray.at(hit.distance))
+                }
+                _ => None,
+            };
+        }
+        let selected: Vec<&pipeline::Draw> = draws
+            .iter()
+            .filter(|draw| self.selection.map(|pick| pick.node) == Some(draw.pick.node))
+            .collect();
+        self.picker.prepare_highlight(device, &selected);
+
+        for draw in &draws {
+            match (self.shading, draw.joints.is_some()) {
+                (Shading::Flat, false) => self.pipeline.prepare(device, draw.key),
+                (Shading::Flat, true) => self.skinned_select_pipeline.prepare(device, draw.key),
+                (Shading::Pbr, false) => self.pbr_pipeline.prepare(device, draw.key),
+                (Shading::Pbr, true) => self.skinned_pipeline.prepare(device, draw.key),
+            }
+        }
 
         let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
         {
             let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
-                    attachment: &frame.view,
+                    attachment: frame,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
                         store: true,
                     },
                 }],
-                depth_stencil_attachment: None,
+                depth_stencil_attachment: Some(self.depth.attachment()),
             });
 
             if let Some(s) = &self.scene {
-                rpass.set_pipeline(&self.pipeline.pipeline);
-                rpass.set_bind_group(0, &self.bind_group, &[0,0]);
-    
-                for mesh in &s.meshes {
-                    for sub in &mesh.subs {
-                        rpass.set_index_buffer(sub.index_buffer.slice(..));
-                        rpass.set_vertex_buffer(0,sub.vertex_buffer.slice(..));
-                        
-                        let range = 0..(sub.count as u32);
-                        rpass.draw_indexed(range, 0, 0..1);
+                let mut current = None;
+                for draw in &draws {
+                    let skinned = draw.joints.is_some();
+                    if current != Some((skinned, draw.key)) {
+                        let pipeline = match (self.shading, skinned) {
+                            (Shading::Flat, false) => &self.pipeline,
+                            (Shading::Flat, true) => &self.skinned_select_pipeline,
+                            (Shading::Pbr, false) => &self.pbr_pipeline,
+                            (Shading::Pbr, true) => &self.skinned_pipeline,
+                        };
+                        rpass.set_pipeline(pipeline.pipeline(&draw.key));
+                        current = Some((skinned, draw.key));
+                    }
+
+                    let offset = pipeline::ModelBuffer::offset(draw.instance);
+                    match (self.shading, draw.joints) {
+                        (Shading::Flat, None) => rpass.set_bind_group(0, &self.bind_group, &[0, 0, offset]),
+                        (Shading::Flat, Some(joints)) => {
+                            let joints = pipeline::JointBuffer::offset(joints);
+                            rpass.set_bind_group(0, &self.skinned_select_bind_group, &[0, 0, offset, joints]);
+                        }
+                        (Shading::Pbr, joints) => {
+                            match joints {
+                                Some(joints) => {
+                                    let joints = pipeline::JointBuffer::offset(joints);
+                                    rpass.set_bind_group(0, &self.skinned_bind_group, &[offset, joints]);
+                                }
+                                None => rpass.set_bind_group(0, &self.pbr_bind_group, &[offset]),
+                            }
+                            rpass.set_bind_group(1, &s.gpu_materials[draw.sub.material].bind_group, &[]);
+                        }
+                    }
+
+                    rpass.set_index_buffer(draw.gpu.index_buffer.slice(..));
+                    rpass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+                    if let (true, Some(skin)) = (skinned, &draw.gpu.skin_buffer) {
+                        rpass.set_vertex_buffer(1, skin.slice(..));
                     }
+
+                    let range = 0..(draw.gpu.count as u32);
+                    rpass.draw_indexed(range, 0, 0..1);
                 }
+
+                self.picker.draw_highlight(&mut rpass, &selected);
             }
         }
         queue.submit(Some(encoder.finish()));
     }
 
     fn mouse_wheel(&mut self, delta: f32) {
-        self.camera.mouse_wheel(delta * 0.1);
+        self.controller.mouse_wheel(delta);
     }
 
     fn mouse_move(&mut self, x: f32, y: f32) {
-        self.camera.mouse_move(x * 0.01, y * 0.01);
+        self.controller.mouse_move(x, y);
     }
 }
+
+impl Example {
+    /// Fits the camera to `sphere`, keeping the viewing direction.
+    fn frame(&mut self, sphere: math::Sphere) {
+        if sphere.is_empty() {
+            return;
+        }
+        let sphere = math::Sphere::new(sphere.center, sphere.radius.max(1e-3));
+        let distance = self.camera.frame_distance(&sphere);
+        self.camera.fit_depth(&sphere, distance);
+        self.controller.frame(sphere.center, distance);
+    }
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    pipeline: &pipeline::PipelineResource,
+    uniform_buffer: &wgpu::Buffer,
+    color_buffer: &wgpu::Buffer,
+    model_buffer: &pipeline::ModelBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &pipeline.bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(color_buffer.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: model_buffer.binding(),
+            },
+        ],
+        label: None,
+    })
+}
+
+fn create_pbr_bind_group(
+    device: &wgpu::Device,
+    pipeline: &pipeline::PipelineResource,
+    uniform_buffer: &wgpu::Buffer,
+    model_buffer: &pipeline::ModelBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &pipeline.bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: model_buffer.binding(),
+            },
+        ],
+        label: None,
+    })
+}
+
+fn create_skinned_bind_group(
+    device: &wgpu::Device,
+    pipeline: &pipeline::PipelineResource,
+    uniform_buffer: &wgpu::Buffer,
+    model_buffer: &pipeline::ModelBuffer,
+    joint_buffer: &pipeline::JointBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &pipeline.bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: model_buffer.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: joint_buffer.binding(),
+            },
+        ],
+        label: None,
+    })
+}
+
+fn create_skinned_select_bind_group(
+    device: &wgpu::Device,
+    pipeline: &pipeline::PipelineResource,
+    uniform_buffer: &wgpu::Buffer,
+    color_buffer: &wgpu::Buffer,
+    model_buffer: &pipeline::ModelBuffer,
+    joint_buffer: &pipeline::JointBuffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout: &pipeline.bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(uniform_buffer.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(color_buffer.slice(..)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: model_buffer.binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: joint_buffer.binding(),
+            },
+        ],
+        label: None,
+    })
+}
+
 fn main() {
-    app::run::<Example>("example");
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--capture") {
+        let path = args.get(i + 1).expect("--capture needs an output file");
+        let mut headless = app::run_headless::<Example>(800, 600).expect("No adapter available for headless rendering!");
+        headless.render();
+        headless.read_frame().save(std::path::Path::new(path)).unwrap();
+    } else if let Some(i) = args.iter().position(|arg| arg == "--precompile-shaders") {
+        // run from a release build so the entries match its optimization level
+        let dir = std::path::PathBuf::from(args.get(i + 1).map(String::as_str).unwrap_or("shaders/precompiled"));
+        for shaders in pipeline::builtin_shaders() {
+            shaders.precompile(&dir).unwrap();
+        }
+        println!("precompiled shaders into {}", dir.display());
+    } else {
+        app::run::<Example>("example");
+    }
 }
\ No newline at end of file
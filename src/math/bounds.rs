@@ -0,0 +1,223 @@
+/// Axis-aligned bounding box, `min > max` when empty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glam::Vec3,
+    pub max: glam::Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: glam::Vec3, max: glam::Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Self {
+            min: glam::Vec3::splat(f32::INFINITY),
+            max: glam::Vec3::splat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = glam::Vec3>) -> Self {
+        points.into_iter().fold(Self::empty(), |aabb, p| aabb.extend(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend(self, point: glam::Vec3) -> Self {
+        Self {
+            min: self.min.min(point),
+            max: self.max.max(point),
+        }
+    }
+
+    pub fn union(self, other: Aabb) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn center(&self) -> glam::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> glam::Vec3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [glam::Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            glam::vec3(a.x, a.y, a.z),
+            glam::vec3(b.x, a.y, a.z),
+            glam::vec3(a.x, b.y, a.z),
+            glam::vec3(b.x, b.y, a.z),
+            glam::vec3(a.x, a.y, b.z),
+            glam::vec3(b.x, a.y, b.z),
+            glam::vec3(a.x, b.y, b.z),
+            glam::vec3(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Box around the transformed corners.
+    pub fn transform(&self, matrix: &glam::Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(self.corners().iter().map(|c| matrix.transform_point3(*c)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sphere {
+    pub center: glam::Vec3,
+    pub radius: f32,
+}
+
+impl Default for Sphere {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Sphere {
+    pub fn new(center: glam::Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Negative radius, the identity of `union`.
+    pub fn empty() -> Self {
+        Self { center: glam::Vec3::zero(), radius: -1.0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius < 0.0
+    }
+
+    /// Centered on `aabb`, tightened to the farthest of `points` when given.
+    pub fn around(aabb: &Aabb, points: impl IntoIterator<Item = glam::Vec3>) -> Self {
+        if aabb.is_empty() {
+            return Self::empty();
+        }
+        let center = aabb.center();
+        let radius = points
+            .into_iter()
+            .map(|p| (p - center).length())
+            .fold(None, |r: Option<f32>, d| Some(r.map_or(d, |r| r.max(d))))
+            .unwrap_or_else(|| aabb.size().length() * 0.5);
+        Self { center, radius }
+    }
+
+    pub fn union(self, other: Sphere) -> Self {
+        if self.is_empty() {
+            return other;
+        }
+        if other.is_empty() {
+            return self;
+        }
+        let offset = other.center - self.center;
+        let distance = offset.length();
+        if distance + other.radius <= self.radius {
+            return self;
+        }
+        if distance + self.radius <= other.radius {
+            return other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        Self { center, radius }
+    }
+
+    /// Conservative under non-uniform scale, the radius grows by the largest axis scale.
+    pub fn transform(&self, matrix: &glam::Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        let scale = matrix.x_axis.truncate().length()
+            .max(matrix.y_axis.truncate().length())
+            .max(matrix.z_axis.truncate().length());
+        Self {
+            center: matrix.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(outer: &Sphere, inner: &Sphere) -> bool {
+        (inner.center - outer.center).length() + inner.radius <= outer.radius + 1e-5
+    }
+
+    #[test]
+    fn sphere_union_contains_both() {
+        let a = Sphere::new(glam::vec3(-2.0, 0.0, 0.0), 1.0);
+        let b = Sphere::new(glam::vec3(3.0, 0.0, 0.0), 2.0);
+        let union = a.union(b);
+        assert!(contains(&union, &a) && contains(&union, &b));
+        // tight for disjoint spheres: spans from -3 to 5
+        assert!((union.radius - 4.0).abs() < 1e-5);
+        assert!((union.center - glam::vec3(1.0, 0.0, 0.0)).length() < 1e-5);
+        assert_eq!(a.union(b), b.union(a));
+    }
+
+    #[test]
+    fn sphere_union_keeps_the_containing_sphere() {
+        let outer = Sphere::new(glam::Vec3::zero(), 5.0);
+        let inner = Sphere::new(glam::vec3(1.0, 1.0, 0.0), 1.0);
+        assert_eq!(outer.union(inner), outer);
+        assert_eq!(inner.union(outer), outer);
+    }
+
+    #[test]
+    fn empty_bounds_are_identities() {
+        let sphere = Sphere::new(glam::vec3(1.0, 2.0, 3.0), 0.5);
+        assert_eq!(Sphere::empty().union(sphere), sphere);
+        assert_eq!(sphere.union(Sphere::empty()), sphere);
+        assert!(Sphere::empty().union(Sphere::empty()).is_empty());
+        assert!(Sphere::empty().transform(&glam::Mat4::from_scale(glam::Vec3::splat(2.0))).is_empty());
+
+        let aabb = Aabb::new(glam::Vec3::zero(), glam::Vec3::one());
+        assert_eq!(Aabb::empty().union(aabb), aabb);
+        assert!(Aabb::empty().is_empty());
+        assert!(Aabb::from_points(Vec::new()).is_empty());
+        assert!(Aabb::empty().transform(&glam::Mat4::from_translation(glam::Vec3::one())).is_empty());
+        assert!(Sphere::around(&Aabb::empty(), Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn transformed_aabb_contains_the_transformed_box() {
+        let aabb = Aabb::new(glam::vec3(-1.0, -1.0, -1.0), glam::vec3(1.0, 2.0, 1.0));
+        let matrix = glam::Mat4::from_scale_rotation_translation(
+            glam::vec3(2.0, 1.0, 1.0),
+            glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+            glam::vec3(10.0, 0.0, 0.0),
+        );
+        let transformed = aabb.transform(&matrix);
+        for corner in aabb.corners().iter() {
+            let p = matrix.transform_point3(*corner);
+            assert!(p.cmpge(transformed.min - glam::Vec3::splat(1e-5)).all());
+            assert!(p.cmple(transformed.max + glam::Vec3::splat(1e-5)).all());
+        }
+        // a plain translation moves the box unchanged
+        let moved = aabb.transform(&glam::Mat4::from_translation(glam::vec3(0.0, 0.0, 5.0)));
+        assert_eq!(moved, Aabb::new(glam::vec3(-1.0, -1.0, 4.0), glam::vec3(1.0, 2.0, 6.0)));
+    }
+
+    #[test]
+    fn disjoint_aabbs_union_to_their_span() {
+        let a = Aabb::new(glam::vec3(-3.0, 0.0, 0.0), glam::vec3(-2.0, 1.0, 1.0));
+        let b = Aabb::new(glam::vec3(2.0, -1.0, 0.0), glam::vec3(4.0, 0.5, 2.0));
+        assert_eq!(a.union(b), Aabb::new(glam::vec3(-3.0, -1.0, 0.0), glam::vec3(4.0, 1.0, 2.0)));
+    }
+}
@@ -0,0 +1,251 @@
+use super::{Aabb, Ray};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Triangle {
+    pub vertices: [glam::Vec3; 3],
+    /// Index into `Mesh::subs`.
+    pub primitive: usize,
+    /// Index of the triangle within its primitive.
+    pub index: usize,
+}
+
+impl Triangle {
+    fn centroid(&self) -> glam::Vec3 {
+        (self.vertices[0] + self.vertices[1] + self.vertices[2]) / 3.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub primitive: usize,
+    pub triangle: usize,
+    /// Weights of the three vertices at the hit point.
+    pub barycentric: glam::Vec3,
+    pub distance: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    aabb: Aabb,
+    /// First child for inner nodes, the second one follows it. First triangle for leaves.
+    start: usize,
+    /// Triangle count of a leaf, 0 for inner nodes.
+    count: usize,
+}
+
+const LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy over the triangles of one mesh, in object space.
+#[derive(Debug, Clone, Default)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<Triangle>,
+}
+
+impl Bvh {
+    /// Splits at the median of the longest centroid axis until leaves hold `LEAF_SIZE` triangles.
+    pub fn new(mut triangles: Vec<Triangle>) -> Self {
+        let mut nodes = Vec::with_capacity(2 * triangles.len() / LEAF_SIZE + 1);
+        if !triangles.is_empty() {
+            let len = triangles.len();
+            nodes.push(BvhNode { aabb: Aabb::empty(), start: 0, count: 0 });
+            build(&mut nodes, &mut triangles, 0, 0, len);
+        }
+        Self { nodes, triangles }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.nodes.first().map_or_else(Aabb::empty, |node| node.aabb)
+    }
+
+    pub fn triangles(&self) -> &[Triangle] {
+        &self.triangles
+    }
+
+    /// Closest triangle hit by `ray` within `max_distance`.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<TriangleHit> {
+        let mut closest: Option<TriangleHit> = None;
+        let mut limit = max_distance;
+        let mut stack = Vec::with_capacity(64);
+        if let Some(root) = self.nodes.first() {
+            if ray.intersect_aabb(&root.aabb, limit).is_some() {
+                stack.push(0);
+            }
+        }
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                for triangle in &self.triangles[node.start..node.start + node.count] {
+                    if let Some((t, u, v)) = ray.intersect_triangle(&triangle.vertices) {
+                        if t < limit {
+                            limit = t;
+                            closest = Some(TriangleHit {
+                                primitive: triangle.primitive,
+                                triangle: triangle.index,
+                                barycentric: glam::vec3(1.0 - u - v, u, v),
+                                distance: t,
+                            });
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // visit the nearer child first so it can shorten the search of the other
+            let (a, b) = (node.start, node.start + 1);
+            let ta = ray.intersect_aabb(&self.nodes[a].aabb, limit);
+            let tb = ray.intersect_aabb(&self.nodes[b].aabb, limit);
+            match (ta, tb) {
+                (Some(ta), Some(tb)) if ta <= tb => stack.extend(&[b, a]),
+                (Some(_), Some(_)) => stack.extend(&[a, b]),
+                (Some(_), None) => stack.push(a),
+                (None, Some(_)) => stack.push(b),
+                (None, None) => {}
+            }
+        }
+        closest
+    }
+}
+
+fn build(nodes: &mut Vec<BvhNode>, triangles: &mut [Triangle], index: usize, start: usize, end: usize) {
+    let slice = &mut triangles[start..end];
+    let aabb = slice.iter().fold(Aabb::empty(), |aabb, t| {
+        aabb.extend(t.vertices[0]).extend(t.vertices[1]).extend(t.vertices[2])
+    });
+    nodes[index].aabb = aabb;
+
+    if slice.len() <= LEAF_SIZE {
+        nodes[index].start = start;
+        nodes[index].count = slice.len();
+        return;
+    }
+
+    let centroids = Aabb::from_points(slice.iter().map(Triangle::centroid));
+    let size = centroids.size();
+    let axis = if size.x >= size.y && size.x >= size.z {
+        0
+    } else if size.y >= size.z {
+        1
+    } else {
+        2
+    };
+    let key = |t: &Triangle| {
+        let c = t.centroid();
+        [c.x, c.y, c.z][axis]
+    };
+    slice.sort_unstable_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal));
+
+    let mid = start + slice.len() / 2;
+    let left = nodes.len();
+    nodes.push(BvhNode { aabb: Aabb::empty(), start: 0, count: 0 });
+    nodes.push(BvhNode { aabb: Aabb::empty(), start: 0, count: 0 });
+    nodes[index].start = left;
+    nodes[index].count = 0;
+    build(nodes, triangles, left, start, mid);
+    build(nodes, triangles, left + 1, mid, end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two unit quads facing +Z, at z = 0 and z = -2, as primitives 0 and 1.
+    fn quads() -> Vec<Triangle> {
+        let mut triangles = Vec::new();
+        for (primitive, z) in [0.0f32, -2.0].iter().enumerate() {
+            let v = |x, y| glam::vec3(x, y, *z);
+            triangles.push(Triangle { vertices: [v(0.0, 0.0), v(1.0, 0.0), v(1.0, 1.0)], primitive, index: 0 });
+            triangles.push(Triangle { vertices: [v(0.0, 0.0), v(1.0, 1.0), v(0.0, 1.0)], primitive, index: 1 });
+        }
+        triangles
+    }
+
+    /// A grid large enough to need inner nodes.
+    fn grid(n: usize) -> Vec<Triangle> {
+        let mut triangles = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let v = |dx: usize, dy: usize| glam::vec3((x + dx) as f32, (y + dy) as f32, 0.0);
+                let index = triangles.len();
+                triangles.push(Triangle { vertices: [v(0, 0), v(1, 0), v(1, 1)], primitive: 0, index });
+                triangles.push(Triangle { vertices: [v(0, 0), v(1, 1), v(0, 1)], primitive: 0, index: index + 1 });
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn tiny_triangles_are_hit() {
+        let triangle = |size: f32| Triangle {
+            vertices: [glam::Vec3::zero(), glam::vec3(size, 0.0, 0.0), glam::vec3(0.0, size, 0.0)],
+            primitive: 0,
+            index: 0,
+        };
+        let mm = 0.001;
+        let ray = Ray::new(glam::vec3(0.25 * mm, 0.25 * mm, 1.0), glam::vec3(0.0, 0.0, -1.0));
+        assert!(Bvh::new(vec![triangle(mm)]).raycast(&ray, f32::INFINITY).is_some());
+
+        // the same triangle modelled in mm and scaled to m
+        let local = ray.transform(&glam::Mat4::from_scale(glam::Vec3::splat(mm)).inverse());
+        let hit = Bvh::new(vec![triangle(1.0)]).raycast(&local, f32::INFINITY).unwrap();
+        assert!((hit.distance - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn closest_hit_wins() {
+        let bvh = Bvh::new(quads());
+        let ray = Ray::new(glam::vec3(0.75, 0.25, 5.0), glam::vec3(0.0, 0.0, -1.0));
+        let hit = bvh.raycast(&ray, f32::INFINITY).unwrap();
+        assert_eq!((hit.primitive, hit.triangle), (0, 0));
+        assert!((hit.distance - 5.0).abs() < 1e-5);
+
+        let b = hit.barycentric;
+        assert!((b.x + b.y + b.z - 1.0).abs() < 1e-5);
+        let [v0, v1, v2] = bvh.triangles().iter().find(|t| t.primitive == 0 && t.index == 0).unwrap().vertices;
+        let point = v0 * b.x + v1 * b.y + v2 * b.z;
+        assert!((point - ray.at(hit.distance)).length() < 1e-5);
+    }
+
+    #[test]
+    fn max_distance_limits_hits() {
+        let bvh = Bvh::new(quads());
+        let ray = Ray::new(glam::vec3(0.25, 0.75, 5.0), glam::vec3(0.0, 0.0, -1.0));
+        assert!(bvh.raycast(&ray, 4.0).is_none());
+        assert_eq!(bvh.raycast(&ray, 6.0).map(|h| h.triangle), Some(1));
+    }
+
+    #[test]
+    fn misses_outside() {
+        let bvh = Bvh::new(quads());
+        let ray = Ray::new(glam::vec3(2.0, 0.5, 5.0), glam::vec3(0.0, 0.0, -1.0));
+        assert!(bvh.raycast(&ray, f32::INFINITY).is_none());
+        let away = Ray::new(glam::vec3(0.5, 0.5, 5.0), glam::vec3(0.0, 0.0, 1.0));
+        assert!(away.intersect_triangle(&quads()[0].vertices).is_none());
+        assert!(bvh.raycast(&away, f32::INFINITY).is_none());
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let triangles = grid(16);
+        let bvh = Bvh::new(triangles.clone());
+        for i in 0..50 {
+            let x = (i as f32 * 0.731) % 16.0;
+            let y = (i as f32 * 1.379) % 16.0;
+            let ray = Ray::new(glam::vec3(x, y, 3.0), glam::vec3(0.1, -0.05, -1.0));
+            let expected = triangles
+                .iter()
+                .filter_map(|t| ray.intersect_triangle(&t.vertices).map(|(d, _, _)| d))
+                .fold(None, |best: Option<f32>, d| Some(best.map_or(d, |b| b.min(d))));
+            let hit = bvh.raycast(&ray, f32::INFINITY).map(|h| h.distance);
+            match (hit, expected) {
+                (Some(hit), Some(expected)) => assert!((hit - expected).abs() < 1e-5, "ray {}", i),
+                (hit, expected) => assert_eq!(hit.is_some(), expected.is_some(), "ray {}", i),
+            }
+        }
+    }
+}
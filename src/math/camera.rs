@@ -1,38 +1,135 @@
 use bytemuck::{Pod, Zeroable};
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in degrees.
+    Perspective { fov: f32, near: f32, far: f32 },
+    /// Height of the view volume in world units, the width follows the aspect ratio.
+    Orthographic { height: f32, near: f32, far: f32 },
+}
+
+impl Projection {
+    pub fn matrix(&self, aspect: f32, reversed_z: bool) -> glam::Mat4 {
+        match *self {
+            Projection::Perspective { fov, near, far } => {
+                let (near, far) = if reversed_z { (far, near) } else { (near, far) };
+                glam::Mat4::perspective_rh(fov.to_radians(), aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let (near, far) = if reversed_z { (far, near) } else { (near, far) };
+                let (w, h) = (height * aspect / 2.0, height / 2.0);
+                glam::Mat4::orthographic_rh(-w, w, -h, h, near, far)
+            }
+        }
+    }
+}
+
+/// Layout of the camera uniform block, `u_Projection` then `u_View`.
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
-pub struct Camera {
+pub struct CameraUniform {
     projection: glam::Mat4,
     transform: glam::Mat4,
 }
 
-unsafe impl Zeroable for Camera {}
-unsafe impl Pod for Camera {}
+unsafe impl Zeroable for CameraUniform {}
+unsafe impl Pod for CameraUniform {}
+
+#[derive(Debug, Copy, Clone)]
+pub struct Camera {
+    pub projection: Projection,
+    aspect: f32,
+    /// Maps the near plane to 1.0 and the far plane to 0.0.
+    pub reversed_z: bool,
+    transform: glam::Mat4,
+}
 
 pub fn perspective(aspect: f32, fov: f32, near: f32, far: f32) -> Camera {
     Camera {
-        projection: glam::Mat4::perspective_rh(fov.to_radians(), aspect, near, far),
+        projection: Projection::Perspective { fov, near, far },
+        aspect,
+        reversed_z: false,
         transform: glam::Mat4::look_at_rh(glam::vec3(3.0,3.0,3.0), glam::Vec3::zero(), glam::Vec3::unit_y()),
     }
 }
 
+/// Perspective projection for reversed-Z depth buffers, near maps to 1.0 and far to 0.0.
+pub fn perspective_reversed(aspect: f32, fov: f32, near: f32, far: f32) -> Camera {
+    Camera {
+        reversed_z: true,
+        ..perspective(aspect, fov, near, far)
+    }
+}
+
 impl Camera {
-    pub fn mouse_move(&mut self, x: f32, y: f32) {
-        let m = glam::Quat::from_rotation_y(x) * glam::Quat::from_rotation_x(y);
-        self.transform = glam::Mat4::from_quat(m) * self.transform;
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = aspect;
     }
 
-    pub fn mouse_wheel(&mut self, delta: f32) {
-        let q = glam::Quat::from_rotation_mat4(&self.transform);
-        let v = delta * super::get_forward_vector(&q);
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        self.set_aspect(width.max(1) as f32 / height.max(1) as f32);
+    }
 
-        self.transform = self.transform * glam::Mat4::from_translation(v);
+    pub fn projection_matrix(&self) -> glam::Mat4 {
+        self.projection.matrix(self.aspect, self.reversed_z)
     }
 
-    // pub fn translate(&mut self, v: &TVec3<T>) {
-    //     self.transform = nalgebra_glm::translate(&self.transform, &v);
-    // }
+    pub fn view(&self) -> glam::Mat4 {
+        self.transform
+    }
+
+    pub fn set_view(&mut self, view: glam::Mat4) {
+        self.transform = view;
+    }
+
+    /// Distance from the center of `sphere` at which it fills the view.
+    pub fn frame_distance(&self, sphere: &super::Sphere) -> f32 {
+        match self.projection {
+            Projection::Perspective { fov, .. } => {
+                let half_y = fov.to_radians() / 2.0;
+                let half_x = (half_y.tan() * self.aspect).atan();
+                sphere.radius / half_y.min(half_x).sin()
+            }
+            Projection::Orthographic { .. } => sphere.radius * 2.0,
+        }
+    }
+
+    /// Moves the clip planes so `sphere` seen from `distance` isn't clipped, with room to zoom out.
+    pub fn fit_depth(&mut self, sphere: &super::Sphere, distance: f32) {
+        let extent = distance + sphere.radius;
+        match &mut self.projection {
+            Projection::Perspective { near, far, .. } => {
+                *near = (extent * 0.0001).max(1e-4);
+                *far = extent * 10.0;
+            }
+            Projection::Orthographic { near, far, .. } => {
+                *near = -extent * 10.0;
+                *far = extent * 10.0;
+            }
+        }
+    }
+
+    /// World space ray through pixel `(x, y)` of a `width` x `height` viewport, starting on the near plane.
+    pub fn ray(&self, x: f32, y: f32, width: u32, height: u32) -> super::Ray {
+        let ndc_x = 2.0 * x / width.max(1) as f32 - 1.0;
+        let ndc_y = 1.0 - 2.0 * y / height.max(1) as f32;
+        let (near_z, far_z) = if self.reversed_z { (1.0, 0.0) } else { (0.0, 1.0) };
+        let inverse = (self.projection_matrix() * self.transform).inverse();
+        let unproject = |z: f32| {
+            let p = inverse * glam::vec4(ndc_x, ndc_y, z, 1.0);
+            p.truncate() / p.w
+        };
+        let near = unproject(near_z);
+        let far = unproject(far_z);
+        super::Ray::new(near, (far - near).normalize())
+    }
+
+    pub fn uniform(&self) -> CameraUniform {
+        CameraUniform {
+            projection: self.projection_matrix(),
+            transform: self.transform,
+        }
+    }
 }
 
 // #[allow(unused)]
@@ -108,4 +205,20 @@ impl Camera {
 //         self.right = self.front.cross(&self.world_up).normalize();
 //         self.up    = self.right.cross(&self.front).normalize();
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn center_ray_follows_view() {
+        for camera in &[perspective(1.5, 45.0, 1.0, 100.0), perspective_reversed(1.5, 45.0, 1.0, 100.0)] {
+            let ray = camera.ray(150.0, 50.0, 300, 100);
+            let forward = -glam::vec3(3.0, 3.0, 3.0).normalize();
+            assert!((ray.direction - forward).length() < 1e-4);
+            // starts on the near plane
+            assert!(((ray.origin - glam::vec3(3.0, 3.0, 3.0)).length() - 1.0).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use winit::event::{ElementState, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent};
+
+/// Turns window input into a view matrix for `Camera::set_view`.
+pub trait CameraController {
+    #[allow(unused_variables)]
+    fn window_event(&mut self, event: &WindowEvent) {}
+
+    /// Raw mouse motion in pixels.
+    #[allow(unused_variables)]
+    fn mouse_move(&mut self, dx: f32, dy: f32) {}
+
+    /// Scroll in lines.
+    #[allow(unused_variables)]
+    fn mouse_wheel(&mut self, delta: f32) {}
+
+    /// Advances smoothing and movement by `dt` seconds.
+    fn update(&mut self, dt: f32);
+
+    /// Still moving without further input, so `update` has to keep being called.
+    fn is_moving(&self) -> bool {
+        false
+    }
+
+    fn view(&self) -> glam::Mat4;
+
+    /// Distance to the point looked at, sizes orthographic views.
+    fn focus_distance(&self) -> Option<f32> {
+        None
+    }
+
+    /// Looks at `center` from `distance` away, keeping the viewing direction.
+    fn frame(&mut self, center: glam::Vec3, distance: f32);
+}
+
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+/// Yaw and pitch of a unit direction, yaw 0 looks down -Z.
+fn yaw_pitch(direction: glam::Vec3) -> (f32, f32) {
+    let yaw = (-direction.x).atan2(-direction.z);
+    let pitch = direction.y.clamp(-1.0, 1.0).asin();
+    (yaw, pitch)
+}
+
+fn direction(yaw: f32, pitch: f32) -> glam::Vec3 {
+    glam::vec3(-yaw.sin() * pitch.cos(), pitch.sin(), -yaw.cos() * pitch.cos())
+}
+
+/// Eye position and forward direction of a view matrix.
+pub fn view_frame(view: &glam::Mat4) -> (glam::Vec3, glam::Vec3) {
+    let world = view.inverse();
+    (world.w_axis.truncate(), -world.z_axis.truncate().normalize())
+}
+
+/// Fraction of the remaining distance covered in `dt`, frame rate independent.
+fn smoothing(damping: f32, dt: f32) -> f32 {
+    if damping <= 0.0 { 1.0 } else { 1.0 - (-dt / damping).exp() }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Orbit {
+    target: glam::Vec3,
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+}
+
+impl Orbit {
+    fn eye(&self) -> glam::Vec3 {
+        self.target - direction(self.yaw, self.pitch) * self.distance
+    }
+}
+
+/// Rotates around a target with the left button, pans with the middle button and zooms with the wheel.
+pub struct OrbitController {
+    current: Orbit,
+    goal: Orbit,
+    /// Seconds to cover ~63% of the way to the goal, 0 disables smoothing.
+    pub damping: f32,
+    /// Radians per pixel.
+    pub rotate_speed: f32,
+    /// Fraction of the distance per pixel.
+    pub pan_speed: f32,
+    /// Zoom factor exponent per wheel line.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    rotating: bool,
+    panning: bool,
+}
+
+impl OrbitController {
+    pub fn looking_at(eye: glam::Vec3, target: glam::Vec3) -> Self {
+        let offset = target - eye;
+        let (yaw, pitch) = yaw_pitch(offset.normalize());
+        let orbit = Orbit {
+            target,
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            distance: offset.length(),
+        };
+        Self {
+            current: orbit,
+            goal: orbit,
+            damping: 0.05,
+            rotate_speed: 0.005,
+            pan_speed: 0.001,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            rotating: false,
+            panning: false,
+        }
+    }
+
+    pub fn target(&self) -> glam::Vec3 {
+        self.goal.target
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.goal.distance
+    }
+
+    /// Moves the goal, keeping the viewing direction.
+    pub fn set_target(&mut self, target: glam::Vec3, distance: f32) {
+        self.goal.target = target;
+        self.goal.distance = distance.max(self.min_distance);
+    }
+
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.goal.yaw -= dx * self.rotate_speed;
+        self.goal.pitch = (self.goal.pitch - dy * self.rotate_speed).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = direction(self.goal.yaw, self.goal.pitch);
+        let right = forward.cross(glam::Vec3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let scale = self.goal.distance * self.pan_speed;
+        self.goal.target += (up * dy - right * dx) * scale;
+    }
+
+    pub fn zoom(&mut self, delta: f32) {
+        self.goal.distance = (self.goal.distance * (-delta * self.zoom_speed).exp()).max(self.min_distance);
+    }
+}
+
+impl CameraController for OrbitController {
+    fn window_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::MouseInput { state, button, .. } = event {
+            let pressed = *state == ElementState::Pressed;
+            match button {
+                MouseButton::Left => self.rotating = pressed,
+                MouseButton::Middle => self.panning = pressed,
+                _ => {}
+            }
+        }
+    }
+
+    fn mouse_move(&mut self, dx: f32, dy: f32) {
+        if self.panning {
+            self.pan(dx, dy);
+        } else if self.rotating {
+            self.rotate(dx, dy);
+        }
+    }
+
+    fn mouse_wheel(&mut self, delta: f32) {
+        self.zoom(delta);
+    }
+
+    fn update(&mut self, dt: f32) {
+        let t = smoothing(self.damping, dt);
+        let (current, goal) = (&mut self.current, &self.goal);
+        current.target = current.target.lerp(goal.target, t);
+        current.yaw += (goal.yaw - current.yaw) * t;
+        current.pitch += (goal.pitch - current.pitch) * t;
+        current.distance += (goal.distance - current.distance) * t;
+    }
+
+    fn is_moving(&self) -> bool {
+        // settled once the eye is within a thousandth of the distance of the goal
+        let (current, goal) = (&self.current, &self.goal);
+        current.eye().distance(goal.eye()) > goal.distance * 1e-3
+            || current.target.distance(goal.target) > goal.distance * 1e-3
+    }
+
+    fn view(&self) -> glam::Mat4 {
+        glam::Mat4::look_at_rh(self.current.eye(), self.current.target, glam::Vec3::unit_y())
+    }
+
+    fn focus_distance(&self) -> Option<f32> {
+        Some(self.current.distance)
+    }
+
+    fn frame(&mut self, center: glam::Vec3, distance: f32) {
+        self.set_target(center, distance);
+    }
+}
+
+/// First-person movement with WASD plus Q/E for down/up, looking around while the right button is held.
+pub struct FlyController {
+    pub position: glam::Vec3,
+    yaw: f32,
+    pitch: f32,
+    /// Units per second, the wheel scales it.
+    pub speed: f32,
+    /// Radians per pixel.
+    pub look_speed: f32,
+    looking: bool,
+    // forward, right, up
+    input: [f32; 3],
+    keys: Vec<VirtualKeyCode>,
+}
+
+impl FlyController {
+    pub fn looking_at(eye: glam::Vec3, target: glam::Vec3) -> Self {
+        let (yaw, pitch) = yaw_pitch((target - eye).normalize());
+        Self {
+            position: eye,
+            yaw,
+            pitch: pitch.clamp(-MAX_PITCH, MAX_PITCH),
+            speed: 2.0,
+            look_speed: 0.003,
+            looking: false,
+            input: [0.0; 3],
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn forward(&self) -> glam::Vec3 {
+        direction(self.yaw, self.pitch)
+    }
+
+    fn update_input(&mut self) {
+        let axis = |keys: &[VirtualKeyCode], positive, negative| {
+            let held = |key| keys.contains(&key) as i32 as f32;
+            held(positive) - held(negative)
+        };
+        self.input = [
+            axis(&self.keys, VirtualKeyCode::W, VirtualKeyCode::S),
+            axis(&self.keys, VirtualKeyCode::D, VirtualKeyCode::A),
+            axis(&self.keys, VirtualKeyCode::E, VirtualKeyCode::Q),
+        ];
+    }
+}
+
+impl CameraController for FlyController {
+    fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::MouseInput { state, button: MouseButton::Right, .. } => {
+                self.looking = *state == ElementState::Pressed;
+            }
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput { virtual_keycode: Some(key), state, .. },
+                ..
+            } => {
+                self.keys.retain(|k| k != key);
+                if *state == ElementState::Pressed {
+                    self.keys.push(*key);
+                }
+                self.update_input();
+            }
+            WindowEvent::Focused(false) => {
+                self.keys.clear();
+                self.looking = false;
+                self.update_input();
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse_move(&mut self, dx: f32, dy: f32) {
+        if self.looking {
+            self.yaw -= dx * self.look_speed;
+            self.pitch = (self.pitch - dy * self.look_speed).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+    }
+
+    fn mouse_wheel(&mut self, delta: f32) {
+        self.speed *= (delta * 0.1).exp();
+    }
+
+    fn update(&mut self, dt: f32) {
+        let forward = self.forward();
+        let right = forward.cross(glam::Vec3::unit_y()).normalize();
+        let [f, r, u] = self.input;
+        self.position += (forward * f + right * r + glam::Vec3::unit_y() * u) * self.speed * dt;
+    }
+
+    fn is_moving(&self) -> bool {
+        self.input != [0.0; 3]
+    }
+
+    fn view(&self) -> glam::Mat4 {
+        glam::Mat4::look_at_rh(self.position, self.position + self.forward(), glam::Vec3::unit_y())
+    }
+
+    fn frame(&mut self, center: glam::Vec3, distance: f32) {
+        self.position = center - self.forward() * distance;
+    }
+}
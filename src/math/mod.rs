@@ -1,11 +1,19 @@
 
+mod bounds;
+mod bvh;
 mod camera;
+mod controller;
+mod ray;
+pub use bounds::{
+    Aabb,Sphere,
+};
+pub use bvh::{
+    Bvh,Triangle,TriangleHit,
+};
 pub use camera::{
-    Camera,perspective,
+    Camera,CameraUniform,Projection,perspective,perspective_reversed,
 };
-
-pub fn get_forward_vector(rotation: &glam::Quat) -> glam::Vec3 {
-    let q = glam::vec3(rotation.x(), rotation.y(), rotation.z());
-    let t = q.cross(glam::Vec3::unit_z()) * 2.0;
-    glam::Vec3::unit_z() + t * rotation.w() + q.cross(t)
-}
\ No newline at end of file
+pub use controller::{
+    CameraController,FlyController,OrbitController,view_frame,
+};
+pub use ray::Ray;
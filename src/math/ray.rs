@@ -0,0 +1,66 @@
+use super::Aabb;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: glam::Vec3,
+    /// Not necessarily normalized, distances are measured in multiples of it.
+    pub direction: glam::Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: glam::Vec3, direction: glam::Vec3) -> Self {
+        Self { origin, direction }
+    }
+
+    pub fn at(&self, t: f32) -> glam::Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Keeps the parametrization, so distances found in the transformed space still apply to `self`.
+    pub fn transform(&self, matrix: &glam::Mat4) -> Self {
+        Self {
+            origin: matrix.transform_point3(self.origin),
+            direction: matrix.transform_vector3(self.direction),
+        }
+    }
+
+    /// Entry distance into `aabb` if it is closer than `max`, 0 when starting inside.
+    pub fn intersect_aabb(&self, aabb: &Aabb, max: f32) -> Option<f32> {
+        let inv = glam::Vec3::one() / self.direction;
+        let t0 = (aabb.min - self.origin) * inv;
+        let t1 = (aabb.max - self.origin) * inv;
+        let near = t0.min(t1);
+        let far = t0.max(t1);
+        let enter = near.x.max(near.y).max(near.z).max(0.0);
+        let exit = far.x.min(far.y).min(far.z).min(max);
+        if enter <= exit { Some(enter) } else { None }
+    }
+
+    /// Möller-Trumbore, hits both faces. Returns the distance and the weights of `b` and `c`.
+    pub fn intersect_triangle(&self, [a, b, c]: &[glam::Vec3; 3]) -> Option<(f32, f32, f32)> {
+        let ab = *b - *a;
+        let ac = *c - *a;
+        let p = self.direction.cross(ac);
+        let det = ab.dot(p);
+        // parallel to the triangle, or degenerate; small triangles have small but normal determinants
+        if !det.is_normal() {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = self.origin - *a;
+        let u = s.dot(p) * inv_det;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+        let q = s.cross(ab);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = ac.dot(q) * inv_det;
+        if t < 0.0 {
+            return None;
+        }
+        Some((t, u, v))
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use crate::{assets, shader};
+use super::{DepthConfig, Result, MESH_BUFFERS};
+
+/// Fixed-function state that differs between the draws of one shader pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub topology: wgpu::PrimitiveTopology,
+    pub cull_mode: wgpu::CullMode,
+    /// Alpha blending with depth writes disabled.
+    pub blend: bool,
+}
+
+impl Default for PipelineKey {
+    fn default() -> Self {
+        Self {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            cull_mode: wgpu::CullMode::Back,
+            blend: false,
+        }
+    }
+}
+
+impl PipelineKey {
+    pub fn new(sub: &assets::SubMesh, material: &assets::Material) -> Self {
+        let triangles = matches!(sub.mode, wgpu::PrimitiveTopology::TriangleList | wgpu::PrimitiveTopology::TriangleStrip);
+        Self {
+            topology: sub.mode,
+            // culling only applies to triangles, don't build extra variants for points and lines
+            cull_mode: if material.double_sided || !triangles {
+                wgpu::CullMode::None
+            } else {
+                wgpu::CullMode::Back
+            },
+            blend: material.alpha_mode == assets::AlphaMode::Blend,
+        }
+    }
+}
+
+/// WGSL vertex shaders aren't reflected, wgpu checks them when the pipeline is created.
+fn check_vertex_inputs(vs: &shader::CompiledShader, vertex_buffers: &[wgpu::VertexBufferDescriptor]) -> shader::Result<()> {
+    match &vs.reflection {
+        Some(reflection) => {
+            let attributes: Vec<_> = vertex_buffers.iter().flat_map(|b| b.attributes.iter().cloned()).collect();
+            reflection.check_vertex_inputs(&attributes)
+        }
+        None => Ok(()),
+    }
+}
+
+/// Lazily builds a render pipeline per `PipelineKey` from one vertex/fragment shader pair.
+pub struct PipelineCache {
+    label: &'static str,
+    shaders: shader::ShaderPair,
+    vs: shader::CompiledShader,
+    fs: shader::CompiledShader,
+    vertex_buffers: &'static [wgpu::VertexBufferDescriptor<'static>],
+    color_format: wgpu::TextureFormat,
+    depth: Option<DepthConfig>,
+    sample_mask: u32,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    /// Fails if the shaders don't compile or don't read `VERTEX_ATTRIBUTES` as laid out.
+    pub fn new(
+        device: &wgpu::Device,
+        label: &'static str,
+        shaders: shader::ShaderPair,
+        color_format: wgpu::TextureFormat,
+        depth: Option<DepthConfig>,
+        sample_mask: u32,
+    ) -> Result<Self> {
+        Self::with_vertex_buffers(device, label, shaders, &MESH_BUFFERS, color_format, depth, sample_mask)
+    }
+
+    /// Like `new`, for shaders that read more than the `GpuMesh` vertex buffer.
+    pub fn with_vertex_buffers(
+        device: &wgpu::Device,
+        label: &'static str,
+        shaders: shader::ShaderPair,
+        vertex_buffers: &'static [wgpu::VertexBufferDescriptor<'static>],
+        color_format: wgpu::TextureFormat,
+        depth: Option<DepthConfig>,
+        sample_mask: u32,
+    ) -> Result<Self> {
+        let (vs, fs) = shaders.compile(device)?;
+        check_vertex_inputs(&vs, vertex_buffers)?;
+        Ok(Self {
+            label,
+            shaders,
+            vs,
+            fs,
+            vertex_buffers,
+            color_format,
+            depth,
+            sample_mask,
+            pipelines: HashMap::new(),
+        })
+    }
+
+    /// Bind group layout entries of `set` as declared by the shaders, which fails for WGSL.
+    pub fn layout_entries(&self, set: u32, dynamic: &[u32]) -> Result<Vec<wgpu::BindGroupLayoutEntry>> {
+        Ok(shader::layout_entries(&[self.vs.interface()?, self.fs.interface()?], set, dynamic)?)
+    }
+
+    pub fn shader_paths(&self) -> Vec<std::path::PathBuf> {
+        self.shaders.paths()
+    }
+
+    /// Recompiles the shaders from disk if one of them is in `changed` and drops the
+    /// pipelines built from the old ones. Keeps everything on a compile error, or when the
+    /// new shaders no longer fit the vertex and bind group layouts.
+    pub fn reload(&mut self, device: &wgpu::Device, changed: &[std::path::PathBuf]) -> bool {
+        let paths = self.shader_paths();
+        if !changed.iter().any(|path| paths.contains(path)) {
+            return false;
+        }
+        let result = self.shaders.reload(device).and_then(|(vs, fs)| {
+            check_vertex_inputs(&vs, self.vertex_buffers)?;
+            let bindings = |shader: &shader::CompiledShader| shader.reflection.as_ref().map(|r| r.bindings.clone());
+            if bindings(&vs) != bindings(&self.vs) || bindings(&fs) != bindings(&self.fs) {
+                return Err(shader::Error::Interface("resource bindings changed, restart to rebuild the bind group layouts".into()));
+            }
+            Ok((vs, fs))
+        });
+        match result {
+            Ok((vs, fs)) => {
+                self.vs = vs;
+                self.fs = fs;
+                self.pipelines.clear();
+                true
+            }
+            Err(e) => {
+                eprintln!("{} shaders failed to reload, keeping the last good pipelines:\n{}", self.label, e);
+                false
+            }
+        }
+    }
+
+    /// Creates the pipeline for `key` unless it already exists.
+    pub fn prepare(&mut self, device: &wgpu::Device, layout: &wgpu::PipelineLayout, key: PipelineKey) {
+        if self.pipelines.contains_key(&key) {
+            return;
+        }
+        let pipeline = self.create(device, layout, key);
+        self.pipelines.insert(key, pipeline);
+    }
+
+    /// Pipeline for `key`, which must have been passed to `prepare` before.
+    pub fn get(&self, key: &PipelineKey) -> &wgpu::RenderPipeline {
+        self.pipelines.get(key).expect("Pipeline variant wasn't prepared!")
+    }
+
+    fn create(&self, device: &wgpu::Device, layout: &wgpu::PipelineLayout, key: PipelineKey) -> wgpu::RenderPipeline {
+        let blend = if key.blend {
+            wgpu::BlendDescriptor {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            }
+        } else {
+            wgpu::BlendDescriptor::REPLACE
+        };
+        let depth_stencil_state = self.depth.map(|d| wgpu::DepthStencilStateDescriptor {
+            depth_write_enabled: d.write && !key.blend,
+            ..d.state()
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+            label: Some(self.label),
+            layout: Some(layout),
+            vertex_state: wgpu::VertexStateDescriptor {
+                index_format: wgpu::IndexFormat::Uint32,
+                vertex_buffers: self.vertex_buffers,
+            },
+            vertex_stage: wgpu::ProgrammableStageDescriptor{
+                module: &self.vs.module,
+                entry_point: "main",
+            },
+            fragment_stage: Some(wgpu::ProgrammableStageDescriptor{
+                module: &self.fs.module,
+                entry_point: "main",
+            }),
+            rasterization_state: Some(wgpu::RasterizationStateDescriptor {
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: key.cull_mode,
+                ..Default::default()
+            }),
+            primitive_topology: key.topology,
+            sample_count: 1,
+            sample_mask: self.sample_mask,
+            color_states: &[wgpu::ColorStateDescriptor {
+                format: self.color_format,
+                color_blend: blend.clone(),
+                alpha_blend: blend,
+                write_mask: wgpu::ColorWrite::ALL,
+            }],
+            depth_stencil_state,
+            alpha_to_coverage_enabled: false,
+        })
+    }
+}
@@ -0,0 +1,10 @@
+// set 0, binding 0, laid out like `math::CameraUniform`
+layout(set = 0, binding = 0) uniform Locals {
+    mat4 u_Projection;
+    mat4 u_View;
+};
+
+// every pass projects through here so their depths match exactly
+vec4 clip_position(vec4 world) {
+    return u_Projection * (u_View * world);
+}
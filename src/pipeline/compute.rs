@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::shader;
+use super::Result;
+
+/// Builds a `ComputeResource`. Bind group layouts and the workgroup size are reflected
+/// from the shader, WGSL shaders have to declare them with `layout` and `workgroup_size`.
+pub struct ComputeBuilder {
+    label: &'static str,
+    shader: shader::ShaderFile,
+    options: shader::CompileOptions,
+    /// `(set, binding)` of buffers bound with a dynamic offset.
+    dynamic: Vec<(u32, u32)>,
+    layouts: HashMap<u32, Vec<wgpu::BindGroupLayoutEntry>>,
+    workgroup_size: Option<[u32; 3]>,
+}
+
+impl ComputeBuilder {
+    pub fn new(label: &'static str, shader: shader::ShaderFile) -> Self {
+        Self {
+            label,
+            shader,
+            options: super::compile_options(),
+            dynamic: Vec::new(),
+            layouts: HashMap::new(),
+            workgroup_size: None,
+        }
+    }
+
+    pub fn options(mut self, options: shader::CompileOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn dynamic(mut self, set: u32, binding: u32) -> Self {
+        self.dynamic.push((set, binding));
+        self
+    }
+
+    /// Uses `entries` for `set` instead of the reflected bindings.
+    pub fn layout(mut self, set: u32, entries: Vec<wgpu::BindGroupLayoutEntry>) -> Self {
+        self.layouts.insert(set, entries);
+        self
+    }
+
+    pub fn workgroup_size(mut self, size: [u32; 3]) -> Self {
+        self.workgroup_size = Some(size);
+        self
+    }
+
+    pub fn build(self, device: &wgpu::Device) -> Result<ComputeResource> {
+        if self.shader.stage != wgpu::ShaderStage::COMPUTE {
+            return Err(shader::Error::UnsupportedStage { name: self.shader.name.to_string(), stage: self.shader.stage }.into());
+        }
+        let compiled = self.shader.compile(device, &self.options)?;
+        let workgroup_size = match (self.workgroup_size, compiled.reflection.as_ref().and_then(|r| r.workgroup_size)) {
+            (Some(size), _) | (None, Some(size)) => size,
+            (None, None) => return Err(format!("{} needs an explicit workgroup size", self.shader.name).into()),
+        };
+
+        let sets = compiled.reflection.iter()
+            .flat_map(|r| r.bindings.iter().map(|b| b.set + 1))
+            .chain(self.layouts.keys().map(|set| set + 1))
+            .max()
+            .unwrap_or(0);
+        let mut bind_group_layouts = Vec::new();
+        for set in 0..sets {
+            let entries = match self.layouts.get(&set) {
+                Some(entries) => entries.clone(),
+                None => {
+                    let dynamic: Vec<u32> = self.dynamic.iter().filter(|(s, _)| *s == set).map(|(_, b)| *b).collect();
+                    shader::layout_entries(&[compiled.interface()?], set, &dynamic)?
+                }
+            };
+            bind_group_layouts.push(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(self.label),
+                entries: &entries,
+            }));
+        }
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(self.label),
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        });
+        let pipeline = create_pipeline(device, self.label, &pipeline_layout, &compiled.module);
+
+        Ok(ComputeResource {
+            label: self.label,
+            shader: self.shader,
+            options: self.options,
+            compiled,
+            workgroup_size,
+            bind_group_layouts,
+            pipeline_layout,
+            pipeline,
+        })
+    }
+}
+
+fn create_pipeline(device: &wgpu::Device, label: &str, layout: &wgpu::PipelineLayout, module: &wgpu::ShaderModule) -> wgpu::ComputePipeline {
+    device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        compute_stage: wgpu::ProgrammableStageDescriptor {
+            module,
+            entry_point: "main",
+        },
+    })
+}
+
+/// A compute pipeline with one bind group layout per descriptor set it uses.
+pub struct ComputeResource {
+    label: &'static str,
+    shader: shader::ShaderFile,
+    options: shader::CompileOptions,
+    compiled: shader::CompiledShader,
+    pub workgroup_size: [u32; 3],
+    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pub pipeline_layout: wgpu::PipelineLayout,
+    pub pipeline: wgpu::ComputePipeline,
+}
+
+impl ComputeResource {
+    /// Workgroups needed to cover `size` invocations.
+    pub fn groups(&self, size: [u32; 3]) -> [u32; 3] {
+        let mut groups = [0; 3];
+        for i in 0..3 {
+            groups[i] = (size[i] + self.workgroup_size[i] - 1) / self.workgroup_size[i];
+        }
+        groups
+    }
+
+    /// Runs one invocation per element of `size`. `bind_groups` are bound from set 0, each with
+    /// the offsets of its `ComputeBuilder::dynamic` bindings in binding order.
+    pub fn dispatch<'a>(
+        &'a self,
+        pass: &mut wgpu::ComputePass<'a>,
+        bind_groups: &[(&'a wgpu::BindGroup, &[wgpu::DynamicOffset])],
+        size: [u32; 3],
+    ) {
+        pass.set_pipeline(&self.pipeline);
+        for (set, (bind_group, offsets)) in bind_groups.iter().enumerate() {
+            pass.set_bind_group(set as u32, bind_group, offsets);
+        }
+        let [x, y, z] = self.groups(size);
+        pass.dispatch(x, y, z);
+    }
+
+    pub fn shader_paths(&self) -> Vec<std::path::PathBuf> {
+        let path = self.shader.path();
+        let dir = path.parent().unwrap_or(&path).to_path_buf();
+        let mut paths = vec![path];
+        paths.extend(self.options.includes.paths(&dir));
+        paths
+    }
+
+    /// Recompiles the shader from disk if it is in `changed`, keeping the old pipeline when it
+    /// fails to compile or its bindings changed.
+    pub fn reload(&mut self, device: &wgpu::Device, changed: &[std::path::PathBuf]) -> bool {
+        let paths = self.shader_paths();
+        if !changed.iter().any(|path| paths.contains(path)) {
+            return false;
+        }
+        let bindings = |shader: &shader::CompiledShader| shader.reflection.as_ref().map(|r| (r.bindings.clone(), r.workgroup_size));
+        let result = self.shader.reload(device, &self.options).and_then(|compiled| {
+            if bindings(&compiled) != bindings(&self.compiled) {
+                return Err(shader::Error::Interface("resource bindings or workgroup size changed, restart to rebuild the pipeline".into()));
+            }
+            Ok(compiled)
+        });
+        match result {
+            Ok(compiled) => {
+                self.pipeline = create_pipeline(device, self.label, &self.pipeline_layout, &compiled.module);
+                self.compiled = compiled;
+                true
+            }
+            Err(e) => {
+                eprintln!("{} shader failed to reload, keeping the last good pipeline:\n{}", self.label, e);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::StorageBuffer;
+    use crate::golden::headless;
+    use crate::shader_file;
+
+    #[test]
+    #[cfg_attr(not(feature = "gpu-tests"), ignore)]
+    fn compute_doubles_storage() {
+        let headless = headless(1, 1);
+        let (device, queue) = (headless.device(), headless.queue());
+        let double = ComputeBuilder::new("double", shader_file!("../../tests/shaders/double.comp", COMPUTE))
+            .build(device)
+            .unwrap();
+        assert_eq!(double.groups([100, 1, 1]), [2, 1, 1]);
+
+        let values: Vec<u32> = (0..100).collect();
+        let buffer = StorageBuffer::new(device, "values", &values, wgpu::BufferUsage::empty());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &double.bind_group_layouts[0],
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.binding() }],
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass();
+            double.dispatch(&mut pass, &[(&bind_group, &[])], [values.len() as u32, 1, 1]);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let doubled: Vec<u32> = values.iter().map(|v| v * 2).collect();
+        assert_eq!(buffer.read(device, queue).unwrap(), doubled);
+    }
+}
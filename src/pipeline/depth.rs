@@ -0,0 +1,95 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthConfig {
+    pub format: wgpu::TextureFormat,
+    pub compare: wgpu::CompareFunction,
+    pub write: bool,
+    /// Maps the near plane to 1.0 and the far plane to 0.0, pair it with `math::perspective_reversed`.
+    pub reversed_z: bool,
+}
+
+impl Default for DepthConfig {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Depth32Float,
+            compare: wgpu::CompareFunction::Less,
+            write: true,
+            reversed_z: false,
+        }
+    }
+}
+
+impl DepthConfig {
+    pub fn reversed() -> Self {
+        Self {
+            reversed_z: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn compare_function(&self) -> wgpu::CompareFunction {
+        if !self.reversed_z {
+            return self.compare;
+        }
+        match self.compare {
+            wgpu::CompareFunction::Less => wgpu::CompareFunction::Greater,
+            wgpu::CompareFunction::LessEqual => wgpu::CompareFunction::GreaterEqual,
+            wgpu::CompareFunction::Greater => wgpu::CompareFunction::Less,
+            wgpu::CompareFunction::GreaterEqual => wgpu::CompareFunction::LessEqual,
+            other => other,
+        }
+    }
+
+    pub fn clear_depth(&self) -> f32 {
+        if self.reversed_z { 0.0 } else { 1.0 }
+    }
+
+    pub fn state(&self) -> wgpu::DepthStencilStateDescriptor {
+        wgpu::DepthStencilStateDescriptor {
+            format: self.format,
+            depth_write_enabled: self.write,
+            depth_compare: self.compare_function(),
+            stencil: wgpu::StencilStateDescriptor::default(),
+        }
+    }
+}
+
+pub struct DepthTexture {
+    pub config: DepthConfig,
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+}
+
+impl DepthTexture {
+    pub fn new(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, config: DepthConfig) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("depth texture"),
+            size: wgpu::Extent3d {
+                width: sc_desc.width,
+                height: sc_desc.height,
+                depth: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        DepthTexture { config, texture, view }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+        *self = DepthTexture::new(device, sc_desc, self.config);
+    }
+
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachmentDescriptor<'_> {
+        wgpu::RenderPassDepthStencilAttachmentDescriptor {
+            attachment: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(self.config.clear_depth()),
+                store: true,
+            }),
+            stencil_ops: None,
+        }
+    }
+}
@@ -0,0 +1,54 @@
+/// Joints a single skinned draw can use, the shaders are compiled with the same limit and
+/// scenes with larger skins fail to load.
+pub const MAX_JOINTS: usize = 64;
+
+const STRIDE: wgpu::BufferAddress = (MAX_JOINTS * 64) as wgpu::BufferAddress;
+
+/// Joint matrices of every skinned draw of a frame, bound with a dynamic offset per draw.
+pub struct JointBuffer {
+    pub buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+impl JointBuffer {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("joint uniform"),
+            size: STRIDE * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        JointBuffer { buffer, capacity }
+    }
+
+    /// Uploads one palette per skinned draw, of at most `MAX_JOINTS` each. Returns true when
+    /// the buffer was reallocated and bind groups using it must be recreated.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, palettes: &[Vec<glam::Mat4>]) -> bool {
+        let grown = palettes.len() > self.capacity;
+        if grown {
+            *self = JointBuffer::new(device, palettes.len().next_power_of_two());
+        }
+
+        let mut data = vec![0u8; STRIDE as usize * palettes.len()];
+        for (i, palette) in palettes.iter().enumerate() {
+            assert!(palette.len() <= MAX_JOINTS, "skin with {} joints wasn't rejected at load time!", palette.len());
+            for (j, joint) in palette.iter().enumerate() {
+                let offset = i * STRIDE as usize + j * 64;
+                data[offset..offset + 64].copy_from_slice(bytemuck::cast_slice(&joint.to_cols_array()));
+            }
+        }
+        if !data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &data);
+        }
+        grown
+    }
+
+    pub fn offset(index: usize) -> wgpu::DynamicOffset {
+        (index as wgpu::BufferAddress * STRIDE) as wgpu::DynamicOffset
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(self.buffer.slice(..STRIDE))
+    }
+}
@@ -1,103 +1,286 @@
-use crate::shader;
+use crate::{assets, shader, shader_file};
+
+mod cache;
+mod compute;
+mod depth;
+mod joints;
+mod model;
+mod pick;
+mod storage;
+pub use cache::{
+    PipelineCache,PipelineKey,
+};
+pub use compute::{
+    ComputeBuilder,ComputeResource,
+};
+pub use depth::{
+    DepthConfig,DepthTexture,
+};
+pub use joints::{
+    JointBuffer,MAX_JOINTS,
+};
+pub use model::ModelBuffer;
+pub use pick::{
+    Draw,Pick,Picker,
+};
+pub use storage::{
+    StorageBuffer,storage_entry,
+};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+// Layout of `assets::VertexData`, shaders are checked against it when compiled.
+const VERTEX_STRIDE: wgpu::BufferAddress = 32;
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttributeDescriptor; 3] = [
+    wgpu::VertexAttributeDescriptor {
+        format: wgpu::VertexFormat::Float3,
+        offset: 0,
+        shader_location: 0,
+    },
+    wgpu::VertexAttributeDescriptor {
+        format: wgpu::VertexFormat::Float3,
+        offset: 12,
+        shader_location: 1,
+    },
+    wgpu::VertexAttributeDescriptor {
+        format: wgpu::VertexFormat::Float2,
+        offset: 24,
+        shader_location: 2,
+    },
+];
+
+// Layout of `assets::SkinVertexData`, in a second vertex buffer.
+const SKIN_STRIDE: wgpu::BufferAddress = 32;
+const SKIN_ATTRIBUTES: [wgpu::VertexAttributeDescriptor; 2] = [
+    wgpu::VertexAttributeDescriptor {
+        format: wgpu::VertexFormat::Uint4,
+        offset: 0,
+        shader_location: 3,
+    },
+    wgpu::VertexAttributeDescriptor {
+        format: wgpu::VertexFormat::Float4,
+        offset: 16,
+        shader_location: 4,
+    },
+];
+
+const MESH_BUFFER: wgpu::VertexBufferDescriptor = wgpu::VertexBufferDescriptor {
+    stride: VERTEX_STRIDE,
+    step_mode: wgpu::InputStepMode::Vertex,
+    attributes: &VERTEX_ATTRIBUTES,
+};
+
+const MESH_BUFFERS: [wgpu::VertexBufferDescriptor; 1] = [MESH_BUFFER];
+
+const SKINNED_BUFFERS: [wgpu::VertexBufferDescriptor; 2] = [
+    MESH_BUFFER,
+    wgpu::VertexBufferDescriptor {
+        stride: SKIN_STRIDE,
+        step_mode: wgpu::InputStepMode::Vertex,
+        attributes: &SKIN_ATTRIBUTES,
+    },
+];
+
+/// Options shared by the built-in pipelines, with the common GLSL available to `#include`.
+fn compile_options() -> shader::CompileOptions {
+    shader::CompileOptions {
+        includes: shader::Includes::embedded(&[
+            ("camera.glsl", include_str!("camera.glsl")),
+            ("model.glsl", include_str!("model.glsl")),
+            ("skin.glsl", include_str!("skin.glsl")),
+            ("lighting.glsl", include_str!("lighting.glsl")),
+        ]),
+        optimization: if cfg!(debug_assertions) {
+            shader::Optimization::Zero
+        } else {
+            shader::Optimization::Performance
+        },
+        cache: shader_cache_dir(),
+        ..Default::default()
+    }
+}
+
+/// `UGRAPHICS_SHADER_CACHE`, compiled shaders are only cached on disk when it is set.
+fn shader_cache_dir() -> Option<std::path::PathBuf> {
+    std::env::var_os("UGRAPHICS_SHADER_CACHE").map(std::path::PathBuf::from)
+}
+
+/// `options` for the skinned variant of a shader, which reads `SKIN_ATTRIBUTES` and a `JointBuffer`.
+fn skinning(options: shader::CompileOptions, skinned: bool) -> shader::CompileOptions {
+    if skinned {
+        options.define("SKINNED", "1").define("MAX_JOINTS", &MAX_JOINTS.to_string())
+    } else {
+        options
+    }
+}
+
+fn select_shaders(skinned: bool) -> shader::ShaderPair {
+    shader::ShaderPair::new(
+        shader_file!("select.vert", VERTEX),
+        shader_file!("select.frag", FRAGMENT),
+        skinning(compile_options().define("MODEL_BINDING", "2").define("JOINTS_BINDING", "3"), skinned),
+    )
+}
+
+fn pbr_shaders(skinned: bool) -> shader::ShaderPair {
+    shader::ShaderPair::new(shader_file!("pbr.vert", VERTEX), shader_file!("pbr.frag", FRAGMENT), skinning(compile_options(), skinned))
+}
+
+/// Every shader the built-in pipelines compile, for precompiling them.
+pub fn builtin_shaders() -> Vec<shader::ShaderPair> {
+    [false, true]
+        .iter()
+        .flat_map(|&skinned| {
+            vec![select_shaders(skinned), pbr_shaders(skinned), pick::pick_shaders(skinned), pick::highlight_shaders(skinned)]
+        })
+        .collect()
+}
+
 pub struct PipelineResource {
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub pipeline_layout: wgpu::PipelineLayout,
-    pub pipeline: wgpu::RenderPipeline,
+    pub pipelines: PipelineCache,
 }
 
-pub fn create_select_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat) -> Result<PipelineResource> {
-    let vs_module = shader::compiler_from_binary(device, include_str!("select.vert"), wgpu::ShaderStage::VERTEX)?;
-    let fg_module = shader::compiler_from_binary(device, include_str!("select.frag"), wgpu::ShaderStage::FRAGMENT)?;
+impl PipelineResource {
+    pub fn prepare(&mut self, device: &wgpu::Device, key: PipelineKey) {
+        self.pipelines.prepare(device, &self.pipeline_layout, key);
+    }
+
+    pub fn pipeline(&self, key: &PipelineKey) -> &wgpu::RenderPipeline {
+        self.pipelines.get(key)
+    }
+
+    pub fn shader_paths(&self) -> Vec<std::path::PathBuf> {
+        self.pipelines.shader_paths()
+    }
+
+    pub fn reload(&mut self, device: &wgpu::Device, changed: &[std::path::PathBuf]) -> bool {
+        self.pipelines.reload(device, changed)
+    }
+}
+
+pub fn create_select_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: Option<DepthConfig>) -> Result<PipelineResource> {
+    let mut pipelines = PipelineCache::new(
+        device,
+        "select",
+        select_shaders(false),
+        color_format,
+        depth,
+        0,
+    )?;
 
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: None,
-        entries: &[
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStage::VERTEX,
-                ty: wgpu::BindingType::UniformBuffer {
-                    dynamic: true,
-                    min_binding_size: wgpu::BufferSize::new(64 * 2),
-                },
-                count: None,
-            },
-            wgpu::BindGroupLayoutEntry {
-                binding: 1,
-                visibility: wgpu::ShaderStage::FRAGMENT,
-                ty: wgpu::BindingType::UniformBuffer {
-                    dynamic: true,
-                    min_binding_size: wgpu::BufferSize::new(16),
-                },
-                count: None,
-            },
-        ],
+        entries: &pipelines.layout_entries(0, &[0, 1, 2])?,
     });
-    
+
     let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         label: None,
         bind_group_layouts: &[&bind_group_layout],
         push_constant_ranges: &[],
     });
+    pipelines.prepare(device, &pipeline_layout, PipelineKey::default());
+
+    Ok(PipelineResource{
+        bind_group_layout,
+        pipeline_layout,
+        pipelines,
+    })
+}
 
-    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor{
+/// Like `create_select_pipeline` for meshes with a skin buffer, set 0 adds the joint matrices
+/// of a `JointBuffer` at binding 3.
+pub fn create_skinned_select_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: Option<DepthConfig>) -> Result<PipelineResource> {
+    let mut pipelines = PipelineCache::with_vertex_buffers(
+        device,
+        "skinned select",
+        select_shaders(true),
+        &SKINNED_BUFFERS,
+        color_format,
+        depth,
+        0,
+    )?;
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: None,
-        layout: Some(&pipeline_layout),
-        vertex_state: wgpu::VertexStateDescriptor {
-            index_format: wgpu::IndexFormat::Uint32,
-            vertex_buffers: &[wgpu::VertexBufferDescriptor {
-                stride: 32 as wgpu::BufferAddress,
-                step_mode: wgpu::InputStepMode::Vertex,
-                attributes: &[
-                    wgpu::VertexAttributeDescriptor {
-                        format: wgpu::VertexFormat::Float3,
-                        offset: 0,
-                        shader_location: 0,
-                    },
-                    wgpu::VertexAttributeDescriptor {
-                        format: wgpu::VertexFormat::Float3,
-                        offset: 12,
-                        shader_location: 1,
-                    },
-                    wgpu::VertexAttributeDescriptor {
-                        format: wgpu::VertexFormat::Float2,
-                        offset: 24,
-                        shader_location: 2,
-                    }
-                ],
-            }],
-        },
-        vertex_stage: wgpu::ProgrammableStageDescriptor{
-            module: &vs_module,
-            entry_point: "main",
-        },
-        fragment_stage: Some(wgpu::ProgrammableStageDescriptor{
-            module: &fg_module,
-            entry_point: "main",
-        }),
-        rasterization_state: Some(wgpu::RasterizationStateDescriptor {
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: wgpu::CullMode::Back,
-            ..Default::default()
-        }),
-        primitive_topology: wgpu::PrimitiveTopology::TriangleList,
-        sample_count: 1,
-        sample_mask: 0,
-        color_states: &[wgpu::ColorStateDescriptor {
-            format: color_format,
-            color_blend: wgpu::BlendDescriptor::REPLACE,
-            alpha_blend: wgpu::BlendDescriptor::REPLACE,
-            write_mask: wgpu::ColorWrite::ALL,
-        }],
-        depth_stencil_state: None,
-        alpha_to_coverage_enabled: false,
+        entries: &pipelines.layout_entries(0, &[0, 1, 2, 3])?,
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
     });
+    pipelines.prepare(device, &pipeline_layout, PipelineKey::default());
 
     Ok(PipelineResource{
         bind_group_layout,
         pipeline_layout,
-        pipeline,
+        pipelines,
     })
-}
\ No newline at end of file
+}
+
+/// Metallic-roughness shading, set 0 holds camera and model, set 1 an `assets::GpuMaterial`.
+pub fn create_pbr_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: Option<DepthConfig>) -> Result<PipelineResource> {
+    let mut pipelines = PipelineCache::new(
+        device,
+        "pbr",
+        pbr_shaders(false),
+        color_format,
+        depth,
+        !0,
+    )?;
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &pipelines.layout_entries(0, &[1])?,
+    });
+    let material_layout = assets::material_bind_group_layout(device);
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bind_group_layout, &material_layout],
+        push_constant_ranges: &[],
+    });
+    pipelines.prepare(device, &pipeline_layout, PipelineKey::default());
+
+    Ok(PipelineResource{
+        bind_group_layout,
+        pipeline_layout,
+        pipelines,
+    })
+}
+
+/// Like `create_pbr_pipeline` for meshes with a skin buffer, set 0 adds the joint matrices
+/// of a `JointBuffer` at binding 2.
+pub fn create_skinned_pipeline(device: &wgpu::Device, color_format: wgpu::TextureFormat, depth: Option<DepthConfig>) -> Result<PipelineResource> {
+    let mut pipelines = PipelineCache::with_vertex_buffers(
+        device,
+        "skinned",
+        pbr_shaders(true),
+        &SKINNED_BUFFERS,
+        color_format,
+        depth,
+        !0,
+    )?;
+
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: None,
+        entries: &pipelines.layout_entries(0, &[1, 2])?,
+    });
+    let material_layout = assets::material_bind_group_layout(device);
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bind_group_layout, &material_layout],
+        push_constant_ranges: &[],
+    });
+    pipelines.prepare(device, &pipeline_layout, PipelineKey::default());
+
+    Ok(PipelineResource{
+        bind_group_layout,
+        pipeline_layout,
+        pipelines,
+    })
+}
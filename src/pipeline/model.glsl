@@ -0,0 +1,18 @@
+// per-draw model matrix and its inverse transpose for normals, at a dynamic offset into `ModelBuffer`
+#ifndef MODEL_BINDING
+#define MODEL_BINDING 1
+#endif
+
+layout(set = 0, binding = MODEL_BINDING) uniform Model {
+    mat4 u_Model;
+    mat4 u_Normal;
+};
+
+vec4 world_position(vec3 position) {
+    return u_Model * vec4(position, 1.0);
+}
+
+// skinned vertices first go through `skin_matrix`
+vec4 world_position(mat4 skin, vec3 position) {
+    return u_Model * (skin * vec4(position, 1.0));
+}
@@ -0,0 +1,81 @@
+/// Model and normal matrices for every draw of a frame, bound with a dynamic offset per draw.
+pub struct ModelBuffer {
+    pub buffer: wgpu::Buffer,
+    capacity: usize,
+}
+
+const STRIDE: wgpu::BufferAddress = wgpu::BIND_BUFFER_ALIGNMENT;
+/// `u_Model` followed by `u_Normal` in model.glsl.
+const SIZE: usize = 128;
+
+/// Inverse transpose of `model`, keeping normals perpendicular under non-uniform scale.
+fn normal_matrix(model: &glam::Mat4) -> glam::Mat4 {
+    let normal = model.inverse().transpose();
+    if normal.to_cols_array().iter().all(|x| x.is_finite()) {
+        normal
+    } else {
+        // flattened models have no inverse, their normals are meaningless anyway
+        *model
+    }
+}
+
+impl ModelBuffer {
+    pub fn new(device: &wgpu::Device, capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("model uniform"),
+            size: STRIDE * capacity as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        ModelBuffer { buffer, capacity }
+    }
+
+    /// Uploads `models`, returns true when the buffer was reallocated and bind groups using it must be recreated.
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, models: &[glam::Mat4]) -> bool {
+        let grown = models.len() > self.capacity;
+        if grown {
+            *self = ModelBuffer::new(device, models.len().next_power_of_two());
+        }
+
+        let mut data = vec![0u8; STRIDE as usize * models.len()];
+        for (i, model) in models.iter().enumerate() {
+            let offset = i * STRIDE as usize;
+            data[offset..offset + 64].copy_from_slice(bytemuck::cast_slice(&model.to_cols_array()));
+            data[offset + 64..offset + SIZE].copy_from_slice(bytemuck::cast_slice(&normal_matrix(model).to_cols_array()));
+        }
+        if !data.is_empty() {
+            queue.write_buffer(&self.buffer, 0, &data);
+        }
+        grown
+    }
+
+    pub fn offset(index: usize) -> wgpu::DynamicOffset {
+        (index as wgpu::BufferAddress * STRIDE) as wgpu::DynamicOffset
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(self.buffer.slice(..SIZE as wgpu::BufferAddress))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::{Mat4, Vec3};
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let model = Mat4::from_scale(Vec3::new(4.0, 1.0, 1.0));
+        // the plane x + y = 0 has normal (1, 1, 0) and contains (1, -1, 0)
+        let tangent = model.transform_vector3(Vec3::new(1.0, -1.0, 0.0));
+        let normal = normal_matrix(&model).transform_vector3(Vec3::new(1.0, 1.0, 0.0));
+        assert!(tangent.dot(normal).abs() < 1e-6);
+    }
+
+    #[test]
+    fn singular_models_keep_the_model_matrix() {
+        let model = Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(normal_matrix(&model), model);
+    }
+}
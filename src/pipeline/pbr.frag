@@ -0,0 +1,84 @@
+#version 450
+
+layout(location = 0) in vec3 v_Position;
+layout(location = 1) in vec3 v_Norm;
+layout(location = 2) in vec2 v_Texcoord;
+
+#include "camera.glsl"
+
+layout(set = 1, binding = 0) uniform Material {
+    vec4 u_BaseColorFactor;
+    vec4 u_EmissiveFactor;
+    // metallic, roughness, normal scale, occlusion strength
+    vec4 u_Factors;
+    // cutoff, mask enabled
+    vec4 u_Alpha;
+};
+layout(set = 1, binding = 1) uniform texture2D t_BaseColor;
+layout(set = 1, binding = 2) uniform sampler s_BaseColor;
+layout(set = 1, binding = 3) uniform texture2D t_MetallicRoughness;
+layout(set = 1, binding = 4) uniform sampler s_MetallicRoughness;
+layout(set = 1, binding = 5) uniform texture2D t_Normal;
+layout(set = 1, binding = 6) uniform sampler s_Normal;
+layout(set = 1, binding = 7) uniform texture2D t_Occlusion;
+layout(set = 1, binding = 8) uniform sampler s_Occlusion;
+layout(set = 1, binding = 9) uniform texture2D t_Emissive;
+layout(set = 1, binding = 10) uniform sampler s_Emissive;
+
+layout(location = 0) out vec4 o_Target;
+
+#include "lighting.glsl"
+
+// normal mapping without vertex tangents, from screen space derivatives
+vec3 perturb_normal(vec3 n, vec3 p, vec2 uv) {
+    vec3 dp1 = dFdx(p);
+    vec3 dp2 = dFdy(p);
+    vec2 duv1 = dFdx(uv);
+    vec2 duv2 = dFdy(uv);
+
+    vec3 dp2perp = cross(dp2, n);
+    vec3 dp1perp = cross(n, dp1);
+    vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+    float invmax = inversesqrt(max(dot(t, t), dot(b, b)));
+    mat3 tbn = mat3(t * invmax, b * invmax, n);
+
+    vec3 map = texture(sampler2D(t_Normal, s_Normal), uv).xyz * 2.0 - 1.0;
+    map.xy *= u_Factors.z;
+    return normalize(tbn * map);
+}
+
+void main() {
+    vec4 base_color = texture(sampler2D(t_BaseColor, s_BaseColor), v_Texcoord) * u_BaseColorFactor;
+    if (u_Alpha.y > 0.5 && base_color.a < u_Alpha.x) {
+        discard;
+    }
+
+    vec4 mr = texture(sampler2D(t_MetallicRoughness, s_MetallicRoughness), v_Texcoord);
+    float metallic = clamp(mr.b * u_Factors.x, 0.0, 1.0);
+    float roughness = clamp(mr.g * u_Factors.y, 0.04, 1.0);
+    float occlusion = mix(1.0, texture(sampler2D(t_Occlusion, s_Occlusion), v_Texcoord).r, u_Factors.w);
+    vec3 emissive = texture(sampler2D(t_Emissive, s_Emissive), v_Texcoord).rgb * u_EmissiveFactor.rgb;
+
+    vec3 camera_position = inverse(u_View)[3].xyz;
+    vec3 n = perturb_normal(normalize(v_Norm), v_Position, v_Texcoord);
+    vec3 v = normalize(camera_position - v_Position);
+    vec3 l = normalize(-LIGHT_DIRECTION);
+    vec3 h = normalize(v + l);
+
+    float n_dot_v = max(dot(n, v), 1e-4);
+    float n_dot_l = max(dot(n, l), 0.0);
+    float n_dot_h = max(dot(n, h), 0.0);
+
+    vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+    vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+    float d = distribution_ggx(n_dot_h, roughness);
+    float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+
+    vec3 specular = d * g * f / (4.0 * n_dot_v * max(n_dot_l, 1e-4));
+    vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base_color.rgb / PI;
+    vec3 color = (diffuse + specular) * LIGHT_COLOR * n_dot_l;
+    color += AMBIENT * base_color.rgb * occlusion + emissive;
+
+    o_Target = vec4(color, base_color.a);
+}
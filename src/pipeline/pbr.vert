@@ -0,0 +1,31 @@
+#version 450
+
+layout(location = 0) in vec3 a_Pos;
+layout(location = 1) in vec3 a_Norm;
+layout(location = 2) in vec2 a_Texcoord;
+
+#include "camera.glsl"
+#include "model.glsl"
+#ifdef SKINNED
+#include "skin.glsl"
+#endif
+
+layout(location = 0) out vec3 v_Position;
+layout(location = 1) out vec3 v_Norm;
+layout(location = 2) out vec2 v_Texcoord;
+
+invariant gl_Position;
+
+void main() {
+#ifdef SKINNED
+    mat4 skin = skin_matrix();
+    vec4 world = world_position(skin, a_Pos);
+    v_Norm = mat3(u_Normal) * mat3(skin) * a_Norm;
+#else
+    vec4 world = world_position(a_Pos);
+    v_Norm = mat3(u_Normal) * a_Norm;
+#endif
+    v_Position = world.xyz;
+    v_Texcoord = a_Texcoord;
+    gl_Position = clip_position(world);
+}
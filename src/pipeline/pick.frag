@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) flat in uint v_Id;
+
+layout(location = 0) out uint o_Id;
+
+void main() {
+    o_Id = v_Id;
+}
@@ -0,0 +1,311 @@
+use crate::{assets, shader, shader_file};
+use super::{DepthConfig, DepthTexture, JointBuffer, ModelBuffer, PipelineCache, PipelineKey, Result, SKINNED_BUFFERS};
+
+/// Object under a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pick {
+    pub node: usize,
+    pub mesh: usize,
+    /// Index into `Mesh::subs`.
+    pub primitive: usize,
+}
+
+/// One submesh drawn with the model matrix at `instance` in the `ModelBuffer`.
+pub struct Draw<'a> {
+    pub instance: usize,
+    /// Palette in the `JointBuffer` for submeshes with a skin buffer.
+    pub joints: Option<usize>,
+    pub pick: Pick,
+    pub sub: &'a assets::SubMesh,
+    pub gpu: &'a assets::GpuMesh,
+    /// `gpu`'s vertex buffer, or the instance's own for morphed meshes.
+    pub vertex_buffer: &'a wgpu::Buffer,
+    pub key: PipelineKey,
+}
+
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Pick and overlay pipelines for either plain or skinned submeshes.
+struct Variant {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    bind_group: Option<wgpu::BindGroup>,
+    pipelines: PipelineCache,
+    highlight: PipelineCache,
+}
+
+impl Variant {
+    fn new(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, depth: DepthConfig, skinned: bool) -> Result<Self> {
+        // the overlay only passes where the selection itself was drawn
+        let overlay_depth = DepthConfig {
+            compare: wgpu::CompareFunction::LessEqual,
+            ..depth
+        };
+        let (buffers, dynamic): (&'static [wgpu::VertexBufferDescriptor<'static>], &[u32]) = if skinned {
+            (&SKINNED_BUFFERS, &[1, 2])
+        } else {
+            (&super::MESH_BUFFERS, &[1])
+        };
+        let pipelines = PipelineCache::with_vertex_buffers(device, "pick", pick_shaders(skinned), buffers, ID_FORMAT, Some(depth), !0)?;
+        let highlight = PipelineCache::with_vertex_buffers(
+            device,
+            "highlight",
+            highlight_shaders(skinned),
+            buffers,
+            sc_desc.format,
+            Some(overlay_depth),
+            !0,
+        )?;
+
+        // both pipelines share the vertex shader and with it the layout
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("pick"),
+            entries: &pipelines.layout_entries(0, dynamic)?,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        Ok(Variant {
+            bind_group_layout,
+            pipeline_layout,
+            bind_group: None,
+            pipelines,
+            highlight,
+        })
+    }
+
+    fn bind(&mut self, device: &wgpu::Device, entries: &[wgpu::BindGroupEntry]) {
+        self.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("pick"),
+            layout: &self.bind_group_layout,
+            entries,
+        }));
+    }
+}
+
+/// Renders draw indices into an `R32Uint` target to find the object under the cursor,
+/// and overlays the selected object.
+pub struct Picker {
+    plain: Variant,
+    skinned: Variant,
+    target: wgpu::Texture,
+    view: wgpu::TextureView,
+    size: (u32, u32),
+    readback: wgpu::Buffer,
+}
+
+/// Binds `draw`'s vertex buffers and model and joint offsets.
+fn bind_draw<'a>(rpass: &mut wgpu::RenderPass<'a>, bind_group: &'a wgpu::BindGroup, draw: &Draw<'a>) {
+    match draw.joints {
+        Some(joints) => {
+            let skin = draw.gpu.skin_buffer.as_ref().expect("Skinned draw without a skin buffer!");
+            rpass.set_bind_group(0, bind_group, &[ModelBuffer::offset(draw.instance), JointBuffer::offset(joints)]);
+            rpass.set_vertex_buffer(1, skin.slice(..));
+        }
+        None => rpass.set_bind_group(0, bind_group, &[ModelBuffer::offset(draw.instance)]),
+    }
+    rpass.set_index_buffer(draw.gpu.index_buffer.slice(..));
+    rpass.set_vertex_buffer(0, draw.vertex_buffer.slice(..));
+}
+
+fn create_target(device: &wgpu::Device, (width, height): (u32, u32)) -> (wgpu::Texture, wgpu::TextureView) {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("pick target"),
+        size: wgpu::Extent3d { width, height, depth: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: ID_FORMAT,
+        usage: wgpu::TextureUsage::OUTPUT_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+    });
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    (texture, view)
+}
+
+pub(super) fn pick_shaders(skinned: bool) -> shader::ShaderPair {
+    shader::ShaderPair::new(
+        shader_file!("pick.vert", VERTEX),
+        shader_file!("pick.frag", FRAGMENT),
+        super::skinning(super::compile_options(), skinned),
+    )
+}
+
+pub(super) fn highlight_shaders(skinned: bool) -> shader::ShaderPair {
+    shader::ShaderPair::new(
+        shader_file!("pick.vert", VERTEX),
+        shader_file!("highlight.frag", FRAGMENT),
+        super::skinning(super::compile_options(), skinned),
+    )
+}
+
+impl Picker {
+    pub fn new(device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor, depth: DepthConfig) -> Result<Self> {
+        let size = (sc_desc.width, sc_desc.height);
+        let (target, view) = create_target(device, size);
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pick readback"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Picker {
+            plain: Variant::new(device, sc_desc, depth, false)?,
+            skinned: Variant::new(device, sc_desc, depth, true)?,
+            target,
+            view,
+            size,
+            readback,
+        })
+    }
+
+    fn variant(&self, draw: &Draw) -> &Variant {
+        if draw.joints.is_some() { &self.skinned } else { &self.plain }
+    }
+
+    fn variant_mut(&mut self, draw: &Draw) -> &mut Variant {
+        if draw.joints.is_some() { &mut self.skinned } else { &mut self.plain }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, sc_desc: &wgpu::SwapChainDescriptor) {
+        self.size = (sc_desc.width, sc_desc.height);
+        let (target, view) = create_target(device, self.size);
+        self.target = target;
+        self.view = view;
+    }
+
+    pub fn shader_paths(&self) -> Vec<std::path::PathBuf> {
+        let mut paths = Vec::new();
+        for variant in &[&self.plain, &self.skinned] {
+            paths.extend(variant.pipelines.shader_paths());
+            paths.extend(variant.highlight.shader_paths());
+        }
+        paths
+    }
+
+    pub fn reload(&mut self, device: &wgpu::Device, changed: &[std::path::PathBuf]) {
+        for variant in &mut [&mut self.plain, &mut self.skinned] {
+            variant.pipelines.reload(device, changed);
+            variant.highlight.reload(device, changed);
+        }
+    }
+
+    /// Must be called again whenever the camera, model or joint buffer is recreated.
+    pub fn bind(&mut self, device: &wgpu::Device, camera: &wgpu::Buffer, models: &ModelBuffer, joints: &JointBuffer) {
+        let camera = wgpu::BindGroupEntry {
+            binding: 0,
+            resource: wgpu::BindingResource::Buffer(camera.slice(..)),
+        };
+        let models = wgpu::BindGroupEntry {
+            binding: 1,
+            resource: models.binding(),
+        };
+        let joints = wgpu::BindGroupEntry {
+            binding: 2,
+            resource: joints.binding(),
+        };
+        self.plain.bind(device, &[camera.clone(), models.clone()]);
+        self.skinned.bind(device, &[camera, models, joints]);
+    }
+
+    /// Renders `draws` and returns the one covering pixel `(x, y)`, clears `depth`.
+    pub fn pick(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        depth: &DepthTexture,
+        draws: &[Draw],
+        (x, y): (u32, u32),
+    ) -> Option<Pick> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+        // ids are integers, blending doesn't apply
+        let key = |draw: &Draw| PipelineKey { blend: false, ..draw.key };
+        for draw in draws {
+            let variant = self.variant_mut(draw);
+            variant.pipelines.prepare(device, &variant.pipeline_layout, key(draw));
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("pick") });
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                    attachment: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: Some(depth.attachment()),
+            });
+            rpass.set_scissor_rect(x, y, 1, 1);
+
+            for (id, draw) in draws.iter().enumerate() {
+                let variant = self.variant(draw);
+                let bind_group = variant.bind_group.as_ref().expect("Picker::bind wasn't called!");
+                rpass.set_pipeline(variant.pipelines.get(&key(draw)));
+                bind_draw(&mut rpass, bind_group, draw);
+                let id = id as u32;
+                rpass.draw_indexed(0..draw.gpu.count as u32, 0, id..id + 1);
+            }
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::TextureCopyView {
+                texture: &self.target,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            wgpu::BufferCopyView {
+                buffer: &self.readback,
+                layout: wgpu::TextureDataLayout {
+                    offset: 0,
+                    bytes_per_row: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT,
+                    rows_per_image: 1,
+                },
+            },
+            wgpu::Extent3d { width: 1, height: 1, depth: 1 },
+        );
+        queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..4);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        let id = match futures::executor::block_on(mapping) {
+            Ok(()) => {
+                let id = bytemuck::cast_slice::<u8, u32>(&slice.get_mapped_range())[0];
+                self.readback.unmap();
+                id
+            }
+            Err(_) => 0,
+        };
+
+        (id as usize).checked_sub(1).and_then(|i| draws.get(i)).map(|draw| draw.pick)
+    }
+
+    /// Creates the overlay pipelines used by `draw_highlight`.
+    pub fn prepare_highlight(&mut self, device: &wgpu::Device, draws: &[&Draw]) {
+        for draw in draws {
+            let variant = self.variant_mut(draw);
+            variant.highlight.prepare(device, &variant.pipeline_layout, PipelineKey { blend: true, ..draw.key });
+        }
+    }
+
+    /// Tints `draws` on top of what the pass already rendered with the same depth buffer.
+    pub fn draw_highlight<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, draws: &[&'a Draw]) {
+        for draw in draws {
+            let variant = self.variant(draw);
+            let bind_group = match &variant.bind_group {
+                Some(bind_group) => bind_group,
+                None => return,
+            };
+            rpass.set_pipeline(variant.highlight.get(&PipelineKey { blend: true, ..draw.key }));
+            bind_draw(rpass, bind_group, draw);
+            rpass.draw_indexed(0..draw.gpu.count as u32, 0, 0..1);
+        }
+    }
+}
@@ -0,0 +1,23 @@
+#version 450
+
+layout(location = 0) in vec3 a_Pos;
+
+#include "camera.glsl"
+#include "model.glsl"
+#ifdef SKINNED
+#include "skin.glsl"
+#endif
+
+// the draw index is passed as the first instance, 0 is left for the background
+layout(location = 0) flat out uint v_Id;
+
+invariant gl_Position;
+
+void main() {
+#ifdef SKINNED
+    gl_Position = clip_position(world_position(skin_matrix(), a_Pos));
+#else
+    gl_Position = clip_position(world_position(a_Pos));
+#endif
+    v_Id = uint(gl_InstanceIndex) + 1u;
+}
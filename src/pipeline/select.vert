@@ -2,18 +2,27 @@
 
 layout(location = 0) in vec3 a_Pos;
 layout(location = 1) in vec3 a_Norm;
-layout(location = 1) in vec2 a_Texcoord;
+layout(location = 2) in vec2 a_Texcoord;
 
-layout(set = 0, binding = 0) uniform Locals {
-    mat4 u_Projection;
-    mat4 u_View;
-};
+#include "camera.glsl"
+#include "model.glsl"
+#ifdef SKINNED
+#include "skin.glsl"
+#endif
 
 layout(location = 0) out vec3 v_Norm;
 layout(location = 1) out vec2 v_Texcoord;
 
+invariant gl_Position;
+
 void main() {
-    gl_Position = u_Projection * u_View * vec4(a_Pos, 1.0);
-    v_Norm = a_Norm;
+#ifdef SKINNED
+    mat4 skin = skin_matrix();
+    gl_Position = clip_position(world_position(skin, a_Pos));
+    v_Norm = mat3(u_Normal) * mat3(skin) * a_Norm;
+#else
+    gl_Position = clip_position(world_position(a_Pos));
+    v_Norm = mat3(u_Normal) * a_Norm;
+#endif
     v_Texcoord = a_Texcoord;
 }
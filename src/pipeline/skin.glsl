@@ -0,0 +1,20 @@
+// joints and weights from the skin buffer and the joint matrices of this draw, at a
+// dynamic offset into `JointBuffer`, for shaders compiled with `SKINNED`
+#ifndef JOINTS_BINDING
+#define JOINTS_BINDING 2
+#endif
+
+layout(location = 3) in uvec4 a_Joints;
+layout(location = 4) in vec4 a_Weights;
+
+layout(set = 0, binding = JOINTS_BINDING) uniform Joints {
+    mat4 u_Joints[MAX_JOINTS];
+};
+
+// moves the vertex from the bind pose, every pass must skin alike for the depths to match
+mat4 skin_matrix() {
+    return a_Weights.x * u_Joints[a_Joints.x]
+        + a_Weights.y * u_Joints[a_Joints.y]
+        + a_Weights.z * u_Joints[a_Joints.z]
+        + a_Weights.w * u_Joints[a_Joints.w];
+}
@@ -0,0 +1,86 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+use wgpu::util::DeviceExt;
+use super::Result;
+
+/// A typed storage buffer, e.g. particles or joint matrices written by compute passes.
+pub struct StorageBuffer<T: Pod> {
+    pub buffer: wgpu::Buffer,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> StorageBuffer<T> {
+    /// `usage` is added to `STORAGE | COPY_DST | COPY_SRC`, e.g. `VERTEX` to draw from it.
+    pub fn new(device: &wgpu::Device, label: &str, data: &[T], usage: wgpu::BufferUsage) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsage::STORAGE | wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::COPY_SRC | usage,
+        });
+        StorageBuffer { buffer, len: data.len(), _marker: PhantomData }
+    }
+
+    pub fn zeroed(device: &wgpu::Device, label: &str, len: usize, usage: wgpu::BufferUsage) -> Self {
+        Self::new(device, label, &vec![T::zeroed(); len], usage)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the elements in bytes, the buffer itself is padded to `COPY_BUFFER_ALIGNMENT`.
+    pub fn size(&self) -> wgpu::BufferAddress {
+        (self.len * std::mem::size_of::<T>()) as wgpu::BufferAddress
+    }
+
+    /// Overwrites the elements from `first` on.
+    pub fn write(&self, queue: &wgpu::Queue, first: usize, data: &[T]) {
+        assert!(first + data.len() <= self.len, "Storage buffer write out of range!");
+        queue.write_buffer(&self.buffer, (first * std::mem::size_of::<T>()) as wgpu::BufferAddress, bytemuck::cast_slice(data));
+    }
+
+    pub fn binding(&self) -> wgpu::BindingResource<'_> {
+        wgpu::BindingResource::Buffer(self.buffer.slice(..))
+    }
+
+    /// Copies the contents back after all submitted work finished, blocks until then.
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<T>> {
+        let size = self.size();
+        let padded = (size + wgpu::COPY_BUFFER_ALIGNMENT - 1) / wgpu::COPY_BUFFER_ALIGNMENT * wgpu::COPY_BUFFER_ALIGNMENT;
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("storage readback"),
+            size: padded.max(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &staging, 0, padded);
+        queue.submit(Some(encoder.finish()));
+
+        let slice = staging.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        futures::executor::block_on(mapping)?;
+
+        let mut data = vec![T::zeroed(); self.len];
+        bytemuck::cast_slice_mut(&mut data).copy_from_slice(&slice.get_mapped_range()[..size as usize]);
+        staging.unmap();
+        Ok(data)
+    }
+}
+
+/// Layout entry for a storage buffer, for shaders whose bindings aren't reflected.
+pub fn storage_entry(binding: u32, visibility: wgpu::ShaderStage, readonly: bool) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::StorageBuffer { dynamic: false, min_binding_size: None, readonly },
+        count: None,
+    }
+}
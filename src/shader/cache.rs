@@ -0,0 +1,134 @@
+use std::path::Path;
+
+use super::{CompileOptions, Includes};
+
+/// Bumped whenever the key or entry layout changes.
+const FORMAT: &str = "u-graphics spirv cache 1";
+
+/// `(key, SPIR-V, dependencies)` embedded by the `precompiled-shaders` feature.
+static PRECOMPILED: &[(&str, &[u8], &str)] = include!(concat!(env!("OUT_DIR"), "/precompiled.rs"));
+
+/// 64 bit FNV-1a, stable across builds unlike `DefaultHasher`.
+pub(super) struct Fnv(u64);
+
+impl Fnv {
+    pub fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Fields are terminated so that `("ab", "c")` and `("a", "bc")` differ.
+    pub fn field(&mut self, bytes: &[u8]) -> &mut Self {
+        for byte in bytes.iter().chain(&[0xff]) {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        self
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn hash(source: &str) -> u64 {
+    Fnv::new().field(source.as_bytes()).finish()
+}
+
+/// Identifies a compile by its inputs. Included files aren't known before compiling,
+/// they are recorded with the entry and checked by `load`.
+pub(super) fn key(source: &str, stage: wgpu::ShaderStage, options: &CompileOptions) -> String {
+    let mut fnv = Fnv::new();
+    fnv.field(FORMAT.as_bytes())
+        .field(format!("{:?}", stage).as_bytes())
+        .field(source.as_bytes())
+        .field(format!("{:?} {:?}", options.optimization, options.target).as_bytes());
+    for (name, value) in &options.defines {
+        fnv.field(name.as_bytes()).field(value.as_deref().unwrap_or("").as_bytes());
+    }
+    format!("{:016x}", fnv.finish())
+}
+
+/// Cached SPIR-V for `key` from the embedded entries or `dir`, if every include still
+/// resolves to the same contents. Entries from another compiler are ignored while one is
+/// available to replace them.
+pub(super) fn load(dir: Option<&Path>, key: &str, includes: &Includes, compiler: Option<&str>) -> Option<Vec<u32>> {
+    let (bytes, deps) = match PRECOMPILED.iter().find(|(k, _, _)| *k == key) {
+        Some((_, bytes, deps)) => (bytes.to_vec(), deps.to_string()),
+        None => {
+            let dir = dir?;
+            let bytes = std::fs::read(dir.join(format!("{}.spv", key))).ok()?;
+            let deps = std::fs::read_to_string(dir.join(format!("{}.deps", key))).ok()?;
+            (bytes, deps)
+        }
+    };
+
+    let mut lines = deps.lines();
+    let built_with = lines.next()?;
+    if compiler.map_or(false, |compiler| compiler != built_with) {
+        return None;
+    }
+    for line in lines {
+        let mut parts = line.splitn(2, ' ');
+        let expected = u64::from_str_radix(parts.next()?, 16).ok()?;
+        let source = includes.read(parts.next()?)?;
+        if hash(&source) != expected {
+            return None;
+        }
+    }
+
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+    Some(bytes.chunks_exact(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect())
+}
+
+/// Writes an entry, `deps` are the resolved includes with their contents.
+pub(super) fn store(dir: &Path, key: &str, spirv: &[u32], deps: &[(String, String)], compiler: &str) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let mut text = format!("{}\n", compiler);
+    for (name, source) in deps {
+        text += &format!("{:016x} {}\n", hash(source), name);
+    }
+    let bytes: Vec<u8> = spirv.iter().flat_map(|w| w.to_le_bytes().to_vec()).collect();
+    // write the SPIR-V last, a crash in between leaves no readable entry
+    std::fs::write(dir.join(format!("{}.deps", key)), text)?;
+    std::fs::write(dir.join(format!("{}.spv", key)), bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("u-graphics-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn key_covers_source_and_defines() {
+        let options = CompileOptions::default();
+        let base = key("void main() {}", wgpu::ShaderStage::VERTEX, &options);
+        assert_eq!(base, key("void main() {}", wgpu::ShaderStage::VERTEX, &options));
+        assert_ne!(base, key("void main() { }", wgpu::ShaderStage::VERTEX, &options));
+        assert_ne!(base, key("void main() {}", wgpu::ShaderStage::FRAGMENT, &options));
+        assert_ne!(base, key("void main() {}", wgpu::ShaderStage::VERTEX, &options.clone().define("A", "1")));
+    }
+
+    #[test]
+    fn stale_includes_miss() {
+        let dir = dir("cache");
+        let includes = Includes::embedded(&[("common.glsl", "float x;")]);
+        let deps = vec![("common.glsl".to_string(), "float x;".to_string())];
+        store(&dir, "k", &[0x0723_0203, 1, 2], &deps, "test").unwrap();
+
+        assert_eq!(load(Some(&dir), "k", &includes, Some("test")), Some(vec![0x0723_0203, 1, 2]));
+        assert_eq!(load(Some(&dir), "k", &includes, None), Some(vec![0x0723_0203, 1, 2]));
+        assert_eq!(load(Some(&dir), "k", &includes, Some("other")), None);
+        assert_eq!(load(Some(&dir), "missing", &includes, None), None);
+
+        let changed = Includes::embedded(&[("common.glsl", "float y;")]);
+        assert_eq!(load(Some(&dir), "k", &changed, None), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::path::PathBuf, std::io::Error),
+    /// GLSL that failed to compile, `message` has the compiler's diagnostics.
+    Compile {
+        name: String,
+        message: String,
+    },
+    UnsupportedStage {
+        name: String,
+        stage: wgpu::ShaderStage,
+    },
+    /// Not in the SPIR-V cache while built without a GLSL compiler.
+    NotPrecompiled(String),
+    /// Source that isn't UTF-8 text, or SPIR-V that isn't a whole number of words.
+    InvalidSource {
+        name: String,
+        reason: &'static str,
+    },
+    /// SPIR-V the reflection can't read.
+    Spirv(String),
+    /// Stages, vertex layouts or bindings that don't fit together.
+    Interface(String),
+    /// A WGSL shader asked for its interface, only SPIR-V is reflected.
+    Unreflected(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "failed to read {}: {}", path.display(), e),
+            Error::Compile { name, message } => write!(f, "failed to compile {}:\n{}", name, message),
+            Error::UnsupportedStage { name, stage } => write!(f, "{} has unsupported stage {:?}", name, stage),
+            Error::NotPrecompiled(name) => {
+                write!(f, "{} isn't precompiled and the runtime-shaders feature is disabled", name)
+            }
+            Error::InvalidSource { name, reason } => write!(f, "{} {}", name, reason),
+            Error::Spirv(message) => write!(f, "invalid SPIR-V: {}", message),
+            Error::Interface(message) => write!(f, "{}", message),
+            Error::Unreflected(name) => write!(f, "{} is WGSL, its interface can't be reflected", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+mod cache;
+mod error;
+mod reflect;
+pub use error::{
+    Error,Result,
+};
+pub use reflect::{
+    Binding,Reflection,Variable,layout_entries,
+};
+
+/// Where `#include` directives are looked up. Quoted includes are tried next to the
+/// including file first, then at the root like `<...>` ones.
+#[derive(Debug, Clone)]
+pub enum Includes {
+    /// Files below a directory.
+    Dir(PathBuf),
+    /// Sources by `/` separated name, e.g. shared code embedded with `include_str!`.
+    Memory(HashMap<String, String>),
+}
+
+impl Default for Includes {
+    fn default() -> Self {
+        Includes::Memory(HashMap::new())
+    }
+}
+
+impl Includes {
+    pub fn embedded(files: &[(&str, &str)]) -> Self {
+        Includes::Memory(files.iter().map(|(name, source)| (name.to_string(), source.to_string())).collect())
+    }
+
+    /// Contents of a name returned by `resolve`.
+    pub fn read(&self, resolved: &str) -> Option<String> {
+        match self {
+            Includes::Dir(root) => std::fs::read_to_string(root.join(resolved)).ok(),
+            Includes::Memory(files) => files.get(resolved).cloned(),
+        }
+    }
+
+    /// Files `resolve` can return, on disk below `dir` for in-memory ones.
+    pub fn paths(&self, dir: &Path) -> Vec<PathBuf> {
+        match self {
+            Includes::Dir(_) => Vec::new(),
+            Includes::Memory(files) => files.keys().map(|name| dir.join(name)).collect(),
+        }
+    }
+
+    /// Name and contents of `requested`, included from `requesting`.
+    pub fn resolve(&self, requested: &str, relative: bool, requesting: &str) -> std::result::Result<(String, String), String> {
+        match self {
+            Includes::Dir(root) => {
+                let sibling = Path::new(requesting).parent().map(|dir| dir.join(requested));
+                let path = sibling
+                    .filter(|path| relative && path.is_file())
+                    .unwrap_or_else(|| root.join(requested));
+                let source = std::fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                Ok((path.display().to_string(), source))
+            }
+            Includes::Memory(files) => {
+                let sibling = requesting.rfind('/').map(|end| normalize(&format!("{}/{}", &requesting[..end], requested)));
+                let name = sibling
+                    .filter(|name| relative && files.contains_key(name))
+                    .unwrap_or_else(|| normalize(requested));
+                match files.get(&name) {
+                    Some(source) => Ok((name, source.clone())),
+                    None => Err(format!("{} not found", requested)),
+                }
+            }
+        }
+    }
+}
+
+/// Collapses `.` and `..` components of a virtual path.
+fn normalize(name: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in name.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Resolved include names with their contents.
+type Resolved = Vec<(String, String)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Optimization {
+    Zero,
+    Size,
+    Performance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetEnv {
+    Vulkan,
+    OpenGl,
+    OpenGlCompat,
+}
+
+/// Preprocessor and code generation settings, `Default` matches shaderc with no includes.
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    /// `#define name value`, or just `#define name`.
+    pub defines: Vec<(String, Option<String>)>,
+    pub includes: Includes,
+    pub optimization: Optimization,
+    /// Environment and version, e.g. `(TargetEnv::Vulkan, 1 << 22)` for Vulkan 1.0.
+    pub target: Option<(TargetEnv, u32)>,
+    /// Directory of the SPIR-V cache, compiled shaders are looked up and stored there.
+    pub cache: Option<PathBuf>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        Self {
+            defines: Vec::new(),
+            includes: Includes::default(),
+            optimization: Optimization::Zero,
+            target: None,
+            cache: None,
+        }
+    }
+}
+
+impl CompileOptions {
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.push((name.to_string(), Some(value.to_string())));
+        self
+    }
+
+    /// Also returns the includes resolved while compiling, filled in as they are read.
+    #[cfg(feature = "runtime-shaders")]
+    fn to_shaderc(&self, name: &str) -> Result<(shaderc::CompileOptions<'static>, std::rc::Rc<std::cell::RefCell<Resolved>>)> {
+        let mut options = shaderc::CompileOptions::new().ok_or_else(|| Error::Compile {
+            name: name.to_string(),
+            message: "failed to create shaderc options".to_string(),
+        })?;
+        for (name, value) in &self.defines {
+            options.add_macro_definition(name, value.as_deref());
+        }
+        options.set_optimization_level(match self.optimization {
+            Optimization::Zero => shaderc::OptimizationLevel::Zero,
+            Optimization::Size => shaderc::OptimizationLevel::Size,
+            Optimization::Performance => shaderc::OptimizationLevel::Performance,
+        });
+        if let Some((env, version)) = self.target {
+            let env = match env {
+                TargetEnv::Vulkan => shaderc::TargetEnv::Vulkan,
+                TargetEnv::OpenGl => shaderc::TargetEnv::OpenGL,
+                TargetEnv::OpenGlCompat => shaderc::TargetEnv::OpenGLCompat,
+            };
+            options.set_target_env(env, version);
+        }
+        let includes = self.includes.clone();
+        let resolved = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let record = resolved.clone();
+        options.set_include_callback(move |requested, ty, requesting, _depth| {
+            let (resolved_name, content) = includes.resolve(requested, ty == shaderc::IncludeType::Relative, requesting)?;
+            record.borrow_mut().push((resolved_name.clone(), content.clone()));
+            Ok(shaderc::ResolvedInclude { resolved_name, content })
+        });
+        Ok((options, resolved))
+    }
+}
+
+/// Source of one shader stage.
+#[derive(Debug, Clone)]
+pub enum ShaderSource<'a> {
+    /// Compiled with shaderc, or taken from the SPIR-V cache.
+    Glsl(Cow<'a, str>),
+    /// Passed to wgpu as is, it isn't reflected.
+    Wgsl(Cow<'a, str>),
+    SpirV(Cow<'a, [u32]>),
+}
+
+impl<'a> ShaderSource<'a> {
+    /// Decodes the contents of `name`, `.wgsl` and `.spv` files are WGSL and SPIR-V, anything else GLSL.
+    pub fn from_bytes(name: &str, bytes: &'a [u8]) -> Result<Self> {
+        let text = || std::str::from_utf8(bytes).map_err(|_| Error::InvalidSource { name: name.to_string(), reason: "isn't UTF-8" });
+        Ok(match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+            Some("wgsl") => ShaderSource::Wgsl(Cow::Borrowed(text()?)),
+            Some("spv") => {
+                if bytes.len() % 4 != 0 {
+                    return Err(Error::InvalidSource { name: name.to_string(), reason: "isn't a whole number of SPIR-V words" });
+                }
+                // include_bytes! only guarantees byte alignment, copy into words
+                let words = bytes.chunks_exact(4).map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]])).collect();
+                ShaderSource::SpirV(Cow::Owned(words))
+            }
+            _ => ShaderSource::Glsl(Cow::Borrowed(text()?)),
+        })
+    }
+}
+
+/// A shader file embedded at build time, which can be re-read from where it was built from.
+#[derive(Debug, Clone, Copy)]
+pub struct ShaderFile {
+    /// Absolute path of the module that embeds the shader.
+    pub module: &'static str,
+    /// File name relative to `module`, its extension picks the language.
+    pub name: &'static str,
+    pub source: &'static [u8],
+    pub stage: wgpu::ShaderStage,
+}
+
+/// Embeds a shader next to the calling module, e.g. `shader_file!("pbr.vert", VERTEX)`.
+#[macro_export]
+macro_rules! shader_file {
+    ($name:literal, $stage:ident) => {
+        $crate::shader::ShaderFile {
+            module: concat!(env!("CARGO_MANIFEST_DIR"), "/", file!()),
+            name: $name,
+            source: include_bytes!($name),
+            stage: wgpu::ShaderStage::$stage,
+        }
+    };
+}
+
+/// A shader module with the interface it was compiled with.
+pub struct CompiledShader {
+    pub name: String,
+    pub module: wgpu::ShaderModule,
+    /// `None` for WGSL, which wgpu validates on its own.
+    pub reflection: Option<Reflection>,
+}
+
+impl CompiledShader {
+    pub fn interface(&self) -> Result<&Reflection> {
+        self.reflection.as_ref().ok_or_else(|| Error::Unreflected(self.name.clone()))
+    }
+}
+
+impl ShaderFile {
+    pub fn path(&self) -> PathBuf {
+        let module = Path::new(self.module);
+        module.parent().unwrap_or(module).join(self.name)
+    }
+
+    /// Compiles the embedded source.
+    pub fn compile(&self, device: &wgpu::Device, options: &CompileOptions) -> Result<CompiledShader> {
+        compile_source(device, self.name, &ShaderSource::from_bytes(self.name, self.source)?, self.stage, options)
+    }
+
+    /// Compiles the current contents on disk, errors name the file and line. Includes
+    /// are read from disk as well, next to the shader.
+    pub fn reload(&self, device: &wgpu::Device, options: &CompileOptions) -> Result<CompiledShader> {
+        let path = self.path();
+        let bytes = std::fs::read(&path).map_err(|e| Error::Io(path.clone(), e))?;
+        let name = path.display().to_string();
+        let options = CompileOptions {
+            includes: Includes::Dir(path.parent().unwrap_or(&path).to_path_buf()),
+            ..options.clone()
+        };
+        compile_source(device, &name, &ShaderSource::from_bytes(&name, &bytes)?, self.stage, &options)
+    }
+
+    /// Compiles the embedded source once per define set, on top of `options`.
+    pub fn compile_permutations(
+        &self,
+        device: &wgpu::Device,
+        options: &CompileOptions,
+        permutations: &[&[(&str, &str)]],
+    ) -> Result<Vec<CompiledShader>> {
+        permutations.iter().map(|defines| {
+            let options = defines.iter().fold(options.clone(), |options, (name, value)| options.define(name, value));
+            self.compile(device, &options)
+        }).collect()
+    }
+}
+
+/// A vertex and fragment shader compiled with the same options.
+#[derive(Debug, Clone)]
+pub struct ShaderPair {
+    pub vs: ShaderFile,
+    pub fs: ShaderFile,
+    pub options: CompileOptions,
+}
+
+/// Checks that the fragment inputs match the vertex outputs, when both were reflected.
+fn check_pair(vs: &CompiledShader, fs: &CompiledShader) -> Result<()> {
+    if let (Some(vs), Some(fs)) = (&vs.reflection, &fs.reflection) {
+        fs.check_inputs_from(vs)?;
+    }
+    Ok(())
+}
+
+impl ShaderPair {
+    pub fn new(vs: ShaderFile, fs: ShaderFile, options: CompileOptions) -> Self {
+        Self { vs, fs, options }
+    }
+
+    /// Compiles both stages and checks that the fragment inputs match the vertex outputs.
+    pub fn compile(&self, device: &wgpu::Device) -> Result<(CompiledShader, CompiledShader)> {
+        let vs = self.vs.compile(device, &self.options)?;
+        let fs = self.fs.compile(device, &self.options)?;
+        check_pair(&vs, &fs)?;
+        Ok((vs, fs))
+    }
+
+    /// Like `compile`, from the sources on disk.
+    pub fn reload(&self, device: &wgpu::Device) -> Result<(CompiledShader, CompiledShader)> {
+        let vs = self.vs.reload(device, &self.options)?;
+        let fs = self.fs.reload(device, &self.options)?;
+        check_pair(&vs, &fs)?;
+        Ok((vs, fs))
+    }
+
+    /// Compiles the GLSL stages into the cache at `dir` without creating modules.
+    pub fn precompile(&self, dir: &Path) -> Result<()> {
+        let options = CompileOptions { cache: Some(dir.to_path_buf()), ..self.options.clone() };
+        for file in &[self.vs, self.fs] {
+            if let ShaderSource::Glsl(source) = ShaderSource::from_bytes(file.name, file.source)? {
+                Reflection::new(file.name, &spirv(&source, file.stage, file.name, &options)?)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sources on disk, including the in-memory includes.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        let vs = self.vs.path();
+        let dir = vs.parent().unwrap_or(&vs).to_path_buf();
+        let mut paths = vec![vs, self.fs.path()];
+        paths.extend(self.options.includes.paths(&dir));
+        paths
+    }
+}
+
+/// Identifies the compiler in cache entries, `None` without one. build.rs takes the shaderc
+/// version from its pin in Cargo.toml.
+fn compiler_version() -> Option<String> {
+    #[cfg(feature = "runtime-shaders")]
+    return Some(format!("{}, spirv {:?}", env!("UG_SHADERC_VERSION"), shaderc::get_spirv_version()));
+    #[cfg(not(feature = "runtime-shaders"))]
+    return None;
+}
+
+/// SPIR-V from the cache, or compiled and added to it.
+fn spirv(source: &str, stage: wgpu::ShaderStage, name: &str, options: &CompileOptions) -> Result<Vec<u32>> {
+    let key = cache::key(source, stage, options);
+    let compiler = compiler_version();
+    if let Some(spirv) = cache::load(options.cache.as_deref(), &key, &options.includes, compiler.as_deref()) {
+        return Ok(spirv);
+    }
+    let (spirv, includes) = compile_glsl(source, stage, name, options)?;
+    if let (Some(dir), Some(compiler)) = (&options.cache, &compiler) {
+        // a failing cache only costs time
+        if let Err(e) = cache::store(dir, &key, &spirv, &includes, compiler) {
+            eprintln!("failed to cache {} in {}: {}", name, dir.display(), e);
+        }
+    }
+    Ok(spirv)
+}
+
+#[cfg(feature = "runtime-shaders")]
+fn compile_glsl(source: &str, stage: wgpu::ShaderStage, name: &str, options: &CompileOptions) -> Result<(Vec<u32>, Resolved)> {
+    let error = |message: String| Error::Compile { name: name.to_string(), message };
+    let mut compiler = shaderc::Compiler::new().ok_or_else(|| error("failed to create the shaderc compiler".to_string()))?;
+
+    let kind = match stage {
+        wgpu::ShaderStage::VERTEX => shaderc::ShaderKind::Vertex,
+        wgpu::ShaderStage::FRAGMENT => shaderc::ShaderKind::Fragment,
+        wgpu::ShaderStage::COMPUTE => shaderc::ShaderKind::Compute,
+        stage => return Err(Error::UnsupportedStage { name: name.to_string(), stage }),
+    };
+
+    let (shaderc_options, includes) = options.to_shaderc(name)?;
+    let binary_result = compiler.compile_into_spirv(
+        source,
+        kind,
+        name,
+        "main",
+        Some(&shaderc_options)).map_err(|e| error(e.to_string()))?;
+
+    let includes = includes.borrow().clone();
+    Ok((binary_result.as_binary().to_vec(), includes))
+}
+
+#[cfg(not(feature = "runtime-shaders"))]
+fn compile_glsl(_source: &str, _stage: wgpu::ShaderStage, name: &str, _options: &CompileOptions) -> Result<(Vec<u32>, Resolved)> {
+    Err(Error::NotPrecompiled(name.to_string()))
+}
+
+/// Creates a module from any source, GLSL goes through the SPIR-V cache. SPIR-V and GLSL
+/// are reflected and have to contain a `stage` entry point.
+pub fn compile_source(
+    device: &wgpu::Device,
+    name: &str,
+    source: &ShaderSource,
+    stage: wgpu::ShaderStage,
+    options: &CompileOptions,
+) -> Result<CompiledShader> {
+    let spirv = match source {
+        ShaderSource::Wgsl(source) => {
+            let module = device.create_shader_module(wgpu::ShaderModuleSource::Wgsl(source.clone()));
+            return Ok(CompiledShader { name: name.to_string(), module, reflection: None });
+        }
+        ShaderSource::Glsl(source) => Cow::Owned(spirv(source, stage, name, options)?),
+        ShaderSource::SpirV(words) => words.clone(),
+    };
+    // validate before handing the module to wgpu, which only reports mismatches at pipeline creation
+    let reflection = Reflection::new(name, &spirv)?;
+    if reflection.stage != stage {
+        return Err(Error::Interface(format!("{} is a {:?} shader, expected {:?}!", name, reflection.stage, stage)));
+    }
+    let module = device.create_shader_module(wgpu::ShaderModuleSource::SpirV(spirv));
+
+    Ok(CompiledShader { name: name.to_string(), module, reflection: Some(reflection) })
+}
+
+/// Polls the modification times of shader files.
+pub struct ShaderWatcher {
+    files: HashMap<PathBuf, Option<std::time::SystemTime>>,
+    interval: std::time::Duration,
+    last_poll: std::time::Instant,
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+impl ShaderWatcher {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        Self {
+            files: paths.into_iter().map(|path| {
+                let time = modified(&path);
+                (path, time)
+            }).collect(),
+            interval: std::time::Duration::from_millis(250),
+            last_poll: std::time::Instant::now(),
+        }
+    }
+
+    /// When `poll` checks the disk again.
+    pub fn next_poll(&self) -> std::time::Instant {
+        self.last_poll + self.interval
+    }
+
+    /// Files written since the last call, checks the disk at most every 250ms.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = std::time::Instant::now();
+
+        let mut changed = Vec::new();
+        for (path, time) in &mut self.files {
+            // editors that save by replacing the file leave it missing for a moment
+            let current = match modified(path) {
+                Some(current) => current,
+                None => continue,
+            };
+            if *time != Some(current) {
+                *time = Some(current);
+                changed.push(path.clone());
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_includes_resolve_relative_first() {
+        let includes = Includes::embedded(&[
+            ("camera.glsl", "// root camera"),
+            ("pbr/camera.glsl", "// pbr camera"),
+            ("pbr/brdf.glsl", "#include \"camera.glsl\""),
+        ]);
+        let resolve = |requested, relative, requesting| includes.resolve(requested, relative, requesting).map(|(name, _)| name);
+        assert_eq!(resolve("camera.glsl", true, "pbr/brdf.glsl"), Ok("pbr/camera.glsl".to_string()));
+        assert_eq!(resolve("camera.glsl", false, "pbr/brdf.glsl"), Ok("camera.glsl".to_string()));
+        assert_eq!(resolve("camera.glsl", true, "pbr.frag"), Ok("camera.glsl".to_string()));
+        assert_eq!(resolve("../camera.glsl", true, "pbr/brdf.glsl"), Ok("camera.glsl".to_string()));
+        assert!(resolve("missing.glsl", true, "pbr.frag").is_err());
+    }
+
+    #[test]
+    fn source_language_follows_extension() {
+        assert!(matches!(ShaderSource::from_bytes("a.frag", b"void main() {}"), Ok(ShaderSource::Glsl(_))));
+        assert!(matches!(ShaderSource::from_bytes("a.wgsl", b"fn main() {}"), Ok(ShaderSource::Wgsl(_))));
+        match ShaderSource::from_bytes("a.spv", &[0x03, 0x02, 0x23, 0x07]) {
+            Ok(ShaderSource::SpirV(words)) => assert_eq!(&words[..], &[0x0723_0203]),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(ShaderSource::from_bytes("a.spv", &[0x03, 0x02]), Err(Error::InvalidSource { .. })));
+        assert!(matches!(ShaderSource::from_bytes("a.vert", &[0xff]), Err(Error::InvalidSource { .. })));
+    }
+}
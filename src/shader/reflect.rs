@@ -0,0 +1,699 @@
+use std::collections::HashMap;
+
+use super::{Error, Result};
+
+/// A stage input or output with an explicit location.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub location: u32,
+    /// `None` for types that don't fit a single vertex attribute, like matrices.
+    pub format: Option<wgpu::VertexFormat>,
+}
+
+/// A resource bound through a descriptor set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    /// Buffers are never dynamic, that isn't visible to the shader.
+    pub ty: wgpu::BindingType,
+    pub count: Option<std::num::NonZeroU32>,
+}
+
+/// Interface of a SPIR-V module as seen by the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reflection {
+    /// Used in error messages.
+    pub name: String,
+    pub stage: wgpu::ShaderStage,
+    pub inputs: Vec<Variable>,
+    pub outputs: Vec<Variable>,
+    pub bindings: Vec<Binding>,
+    /// `local_size` of compute shaders.
+    pub workgroup_size: Option<[u32; 3]>,
+}
+
+#[derive(Debug, Clone)]
+enum Type {
+    Scalar { float: bool, signed: bool, width: u32 },
+    Vector { component: u32, count: u32 },
+    Matrix { column: u32, count: u32 },
+    Image { sampled_type: u32, dim: u32, arrayed: bool, multisampled: bool, sampled: u32, format: u32 },
+    Sampler,
+    SampledImage,
+    Array { element: u32, length: u32 },
+    RuntimeArray { element: u32 },
+    Struct { members: Vec<u32> },
+    Pointer { pointee: u32 },
+    Other,
+}
+
+#[derive(Default)]
+struct Decorations {
+    location: Option<u32>,
+    set: Option<u32>,
+    binding: Option<u32>,
+    block: bool,
+    buffer_block: bool,
+    non_writable: bool,
+    array_stride: Option<u32>,
+}
+
+#[derive(Default)]
+struct MemberDecorations {
+    offset: u32,
+    matrix_stride: Option<u32>,
+    non_writable: bool,
+}
+
+const MAGIC: u32 = 0x0723_0203;
+
+// opcodes
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_FUNCTION: u32 = 54;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+// execution modes
+const LOCAL_SIZE: u32 = 17;
+
+// decorations
+const BLOCK: u32 = 2;
+const BUFFER_BLOCK: u32 = 3;
+const ARRAY_STRIDE: u32 = 6;
+const MATRIX_STRIDE: u32 = 7;
+const NON_WRITABLE: u32 = 24;
+const LOCATION: u32 = 30;
+const BINDING: u32 = 33;
+const DESCRIPTOR_SET: u32 = 34;
+const OFFSET: u32 = 35;
+
+// storage classes
+const UNIFORM_CONSTANT: u32 = 0;
+const INPUT: u32 = 1;
+const UNIFORM: u32 = 2;
+const OUTPUT: u32 = 3;
+const STORAGE_BUFFER: u32 = 12;
+
+fn string(words: &[u32]) -> String {
+    let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes().to_vec()).take_while(|b| *b != 0).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+struct Module {
+    names: HashMap<u32, String>,
+    types: HashMap<u32, Type>,
+    constants: HashMap<u32, u32>,
+    decorations: HashMap<u32, Decorations>,
+    members: HashMap<(u32, u32), MemberDecorations>,
+    /// `(id, pointer type, storage class)`
+    variables: Vec<(u32, u32, u32)>,
+    stage: Option<wgpu::ShaderStage>,
+    workgroup_size: Option<[u32; 3]>,
+}
+
+impl Module {
+    fn parse(spirv: &[u32]) -> Result<Self> {
+        if spirv.len() < 5 || spirv[0] != MAGIC {
+            return Err(Error::Spirv("Not a SPIR-V module!".into()));
+        }
+        let mut module = Module {
+            names: HashMap::new(),
+            types: HashMap::new(),
+            constants: HashMap::new(),
+            decorations: HashMap::new(),
+            members: HashMap::new(),
+            variables: Vec::new(),
+            stage: None,
+            workgroup_size: None,
+        };
+
+        let mut words = &spirv[5..];
+        while !words.is_empty() {
+            let count = (words[0] >> 16) as usize;
+            let opcode = words[0] & 0xffff;
+            if count == 0 || count > words.len() {
+                return Err(Error::Spirv("Truncated SPIR-V instruction!".into()));
+            }
+            let ops = &words[1..count];
+            words = &words[count..];
+
+            match opcode {
+                // types, variables and decorations all precede the function bodies
+                OP_FUNCTION => break,
+                OP_NAME => {
+                    module.names.insert(ops[0], string(&ops[1..]));
+                }
+                OP_ENTRY_POINT if module.stage.is_none() => {
+                    module.stage = match ops[0] {
+                        0 => Some(wgpu::ShaderStage::VERTEX),
+                        4 => Some(wgpu::ShaderStage::FRAGMENT),
+                        5 => Some(wgpu::ShaderStage::COMPUTE),
+                        model => return Err(Error::Spirv(format!("Unsupported execution model {}!", model))),
+                    };
+                }
+                OP_EXECUTION_MODE if ops.len() >= 5 && ops[1] == LOCAL_SIZE => {
+                    module.workgroup_size = Some([ops[2], ops[3], ops[4]]);
+                }
+                OP_TYPE_BOOL => {
+                    module.types.insert(ops[0], Type::Other);
+                }
+                OP_TYPE_INT => {
+                    module.types.insert(ops[0], Type::Scalar { float: false, signed: ops[2] != 0, width: ops[1] });
+                }
+                OP_TYPE_FLOAT => {
+                    module.types.insert(ops[0], Type::Scalar { float: true, signed: true, width: ops[1] });
+                }
+                OP_TYPE_VECTOR => {
+                    module.types.insert(ops[0], Type::Vector { component: ops[1], count: ops[2] });
+                }
+                OP_TYPE_MATRIX => {
+                    module.types.insert(ops[0], Type::Matrix { column: ops[1], count: ops[2] });
+                }
+                OP_TYPE_IMAGE => {
+                    module.types.insert(ops[0], Type::Image {
+                        sampled_type: ops[1],
+                        dim: ops[2],
+                        arrayed: ops[4] != 0,
+                        multisampled: ops[5] != 0,
+                        sampled: ops[6],
+                        format: ops[7],
+                    });
+                }
+                OP_TYPE_SAMPLER => {
+                    module.types.insert(ops[0], Type::Sampler);
+                }
+                OP_TYPE_SAMPLED_IMAGE => {
+                    module.types.insert(ops[0], Type::SampledImage);
+                }
+                OP_TYPE_ARRAY => {
+                    module.types.insert(ops[0], Type::Array { element: ops[1], length: ops[2] });
+                }
+                OP_TYPE_RUNTIME_ARRAY => {
+                    module.types.insert(ops[0], Type::RuntimeArray { element: ops[1] });
+                }
+                OP_TYPE_STRUCT => {
+                    module.types.insert(ops[0], Type::Struct { members: ops[1..].to_vec() });
+                }
+                OP_TYPE_POINTER => {
+                    module.types.insert(ops[0], Type::Pointer { pointee: ops[2] });
+                }
+                OP_CONSTANT => {
+                    module.constants.insert(ops[1], ops[2]);
+                }
+                OP_VARIABLE => {
+                    module.variables.push((ops[1], ops[0], ops[2]));
+                }
+                OP_DECORATE => {
+                    let decorations = module.decorations.entry(ops[0]).or_default();
+                    match ops[1] {
+                        BLOCK => decorations.block = true,
+                        BUFFER_BLOCK => decorations.buffer_block = true,
+                        NON_WRITABLE => decorations.non_writable = true,
+                        ARRAY_STRIDE => decorations.array_stride = Some(ops[2]),
+                        LOCATION => decorations.location = Some(ops[2]),
+                        BINDING => decorations.binding = Some(ops[2]),
+                        DESCRIPTOR_SET => decorations.set = Some(ops[2]),
+                        _ => {}
+                    }
+                }
+                OP_MEMBER_DECORATE => {
+                    let member = module.members.entry((ops[0], ops[1])).or_default();
+                    match ops[2] {
+                        OFFSET => member.offset = ops[3],
+                        MATRIX_STRIDE => member.matrix_stride = Some(ops[3]),
+                        NON_WRITABLE => member.non_writable = true,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(module)
+    }
+
+    fn ty(&self, id: u32) -> Result<&Type> {
+        self.types.get(&id).ok_or_else(|| Error::Spirv(format!("Unknown SPIR-V type %{}!", id)))
+    }
+
+    fn name(&self, id: u32) -> String {
+        self.names.get(&id).cloned().unwrap_or_else(|| format!("%{}", id))
+    }
+
+    fn array_length(&self, id: u32) -> Result<u32> {
+        self.constants.get(&id).cloned().ok_or_else(|| Error::Spirv(format!("Array length %{} isn't a constant!", id)))
+    }
+
+    /// Bytes covered by a value of type `id`, `matrix_stride` applies to matrices in blocks.
+    fn size(&self, id: u32, matrix_stride: Option<u32>) -> Result<u64> {
+        Ok(match self.ty(id)? {
+            Type::Scalar { width, .. } => *width as u64 / 8,
+            Type::Vector { component, count } => self.size(*component, None)? * *count as u64,
+            Type::Matrix { column, count } => {
+                let stride = match matrix_stride {
+                    Some(stride) => stride as u64,
+                    None => self.size(*column, None)?,
+                };
+                stride * *count as u64
+            }
+            Type::Array { element, length } => {
+                let stride = match self.decorations.get(&id).and_then(|d| d.array_stride) {
+                    Some(stride) => stride as u64,
+                    None => self.size(*element, matrix_stride)?,
+                };
+                stride * self.array_length(*length)? as u64
+            }
+            // at least one element has to be bound
+            Type::RuntimeArray { element } => match self.decorations.get(&id).and_then(|d| d.array_stride) {
+                Some(stride) => stride as u64,
+                None => self.size(*element, matrix_stride)?,
+            },
+            Type::Struct { members } => {
+                let mut end = 0;
+                for (index, member) in members.iter().enumerate() {
+                    let decorations = self.members.get(&(id, index as u32));
+                    let offset = decorations.map_or(0, |d| d.offset) as u64;
+                    let size = self.size(*member, decorations.and_then(|d| d.matrix_stride))?;
+                    end = end.max(offset + size);
+                }
+                end
+            }
+            _ => 0,
+        })
+    }
+
+    fn vertex_format(&self, id: u32) -> Option<wgpu::VertexFormat> {
+        let (component, count) = match self.ty(id).ok()? {
+            Type::Vector { component, count } => (*component, *count),
+            Type::Scalar { .. } => (id, 1),
+            _ => return None,
+        };
+        use wgpu::VertexFormat::*;
+        let formats = match self.ty(component).ok()? {
+            Type::Scalar { float: true, width: 32, .. } => [Float, Float2, Float3, Float4],
+            Type::Scalar { float: false, signed: true, width: 32 } => [Int, Int2, Int3, Int4],
+            Type::Scalar { float: false, signed: false, width: 32 } => [Uint, Uint2, Uint3, Uint4],
+            _ => return None,
+        };
+        formats.get(count as usize - 1).cloned()
+    }
+
+    fn binding_type(&self, var: u32, ty: u32, storage: u32) -> Result<wgpu::BindingType> {
+        let name = self.name(var);
+        let decorations = self.decorations.get(&ty);
+        let non_writable = self.decorations.get(&var).map_or(false, |d| d.non_writable);
+        Ok(match self.ty(ty)? {
+            Type::Struct { members } => {
+                let min_binding_size = wgpu::BufferSize::new(self.size(ty, None)?);
+                let block = decorations.map_or(false, |d| d.block);
+                let buffer_block = decorations.map_or(false, |d| d.buffer_block);
+                if storage == UNIFORM && block {
+                    wgpu::BindingType::UniformBuffer { dynamic: false, min_binding_size }
+                } else if storage == STORAGE_BUFFER || buffer_block {
+                    let readonly = non_writable || (0..members.len() as u32)
+                        .all(|i| self.members.get(&(ty, i)).map_or(false, |m| m.non_writable));
+                    wgpu::BindingType::StorageBuffer { dynamic: false, min_binding_size, readonly }
+                } else {
+                    return Err(Error::Spirv(format!("{} isn't a uniform or storage block!", name)));
+                }
+            }
+            Type::Image { sampled_type, dim, arrayed, multisampled, sampled, format } => {
+                let dimension = match (dim, arrayed) {
+                    (0, false) => wgpu::TextureViewDimension::D1,
+                    (1, false) => wgpu::TextureViewDimension::D2,
+                    (1, true) => wgpu::TextureViewDimension::D2Array,
+                    (2, false) => wgpu::TextureViewDimension::D3,
+                    (3, false) => wgpu::TextureViewDimension::Cube,
+                    (3, true) => wgpu::TextureViewDimension::CubeArray,
+                    _ => return Err(Error::Spirv(format!("{} has an unsupported image dimension!", name))),
+                };
+                if *sampled == 2 {
+                    let format = storage_format(*format)
+                        .ok_or_else(|| Error::Spirv(format!("{} has an unsupported storage image format!", name)))?;
+                    wgpu::BindingType::StorageTexture { dimension, format, readonly: non_writable }
+                } else {
+                    let component_type = match self.ty(*sampled_type)? {
+                        Type::Scalar { float: true, .. } => wgpu::TextureComponentType::Float,
+                        Type::Scalar { signed: true, .. } => wgpu::TextureComponentType::Sint,
+                        _ => wgpu::TextureComponentType::Uint,
+                    };
+                    wgpu::BindingType::SampledTexture { dimension, component_type, multisampled: *multisampled }
+                }
+            }
+            // comparison samplers look the same in SPIR-V
+            Type::Sampler => wgpu::BindingType::Sampler { comparison: false },
+            Type::SampledImage => {
+                return Err(Error::Spirv(format!("{} is a combined image sampler, use separate texture and sampler bindings!", name)))
+            }
+            _ => return Err(Error::Spirv(format!("{} has an unsupported resource type!", name))),
+        })
+    }
+}
+
+/// Maps the SPIR-V image formats wgpu can bind as storage textures.
+fn storage_format(format: u32) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat::*;
+    Some(match format {
+        1 => Rgba32Float,
+        2 => Rgba16Float,
+        3 => R32Float,
+        4 => Rgba8Unorm,
+        5 => Rgba8Snorm,
+        6 => Rg32Float,
+        7 => Rg16Float,
+        21 => Rgba32Sint,
+        22 => Rgba16Sint,
+        23 => Rgba8Sint,
+        24 => R32Sint,
+        25 => Rg32Sint,
+        30 => Rgba32Uint,
+        31 => Rgba16Uint,
+        32 => Rgba8Uint,
+        33 => R32Uint,
+        35 => Rg32Uint,
+        _ => return None,
+    })
+}
+
+impl Reflection {
+    pub fn new(name: &str, spirv: &[u32]) -> Result<Self> {
+        let module = Module::parse(spirv)?;
+        let stage = module.stage.ok_or_else(|| Error::Spirv(format!("{} has no entry point!", name)))?;
+        let mut reflection = Reflection {
+            name: name.to_string(),
+            stage,
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            bindings: Vec::new(),
+            workgroup_size: module.workgroup_size,
+        };
+
+        for &(var, pointer, storage) in &module.variables {
+            let pointee = match module.ty(pointer)? {
+                Type::Pointer { pointee } => *pointee,
+                _ => return Err(Error::Spirv(format!("Variable %{} isn't a pointer!", var))),
+            };
+            let decorations = module.decorations.get(&var);
+            match storage {
+                INPUT | OUTPUT => {
+                    // built-ins have no location
+                    let location = match decorations.and_then(|d| d.location) {
+                        Some(location) => location,
+                        None => continue,
+                    };
+                    let variable = Variable { name: module.name(var), location, format: module.vertex_format(pointee) };
+                    if storage == INPUT {
+                        reflection.inputs.push(variable);
+                    } else {
+                        reflection.outputs.push(variable);
+                    }
+                }
+                UNIFORM_CONSTANT | UNIFORM | STORAGE_BUFFER => {
+                    let (ty, count) = match module.ty(pointee)? {
+                        Type::Array { element, length } => (*element, std::num::NonZeroU32::new(module.array_length(*length)?)),
+                        _ => (pointee, None),
+                    };
+                    reflection.bindings.push(Binding {
+                        name: module.name(var),
+                        set: decorations.and_then(|d| d.set).unwrap_or(0),
+                        binding: decorations.and_then(|d| d.binding).unwrap_or(0),
+                        ty: module.binding_type(var, ty, storage)?,
+                        count,
+                    });
+                }
+                _ => {}
+            }
+        }
+        reflection.inputs.sort_by_key(|v| v.location);
+        reflection.outputs.sort_by_key(|v| v.location);
+        reflection.bindings.sort_by_key(|b| (b.set, b.binding));
+        reflection.check_locations()?;
+        Ok(reflection)
+    }
+
+    /// Two inputs or two outputs sharing a location silently alias each other.
+    fn check_locations(&self) -> Result<()> {
+        for (kind, variables) in &[("inputs", &self.inputs), ("outputs", &self.outputs)] {
+            for pair in variables.windows(2) {
+                if pair[0].location == pair[1].location {
+                    return Err(Error::Interface(format!(
+                        "{}: {} {} and {} share location {}!",
+                        self.name, kind, pair[0].name, pair[1].name, pair[0].location,
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every vertex input is fed by an attribute of the same format.
+    pub fn check_vertex_inputs(&self, attributes: &[wgpu::VertexAttributeDescriptor]) -> Result<()> {
+        for input in &self.inputs {
+            match attributes.iter().find(|a| a.shader_location == input.location) {
+                Some(attribute) if Some(attribute.format) == input.format => {}
+                Some(attribute) => {
+                    return Err(Error::Interface(format!(
+                        "{}: {} at location {} is {:?}, the vertex buffer provides {:?}!",
+                        self.name, input.name, input.location, input.format, attribute.format,
+                    )))
+                }
+                None => {
+                    return Err(Error::Interface(format!(
+                        "{}: no vertex attribute at location {} for {}!",
+                        self.name, input.location, input.name,
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every input of `self` is written by `previous` with the same type.
+    pub fn check_inputs_from(&self, previous: &Reflection) -> Result<()> {
+        for input in &self.inputs {
+            match previous.outputs.iter().find(|o| o.location == input.location) {
+                Some(output) if output.format == input.format => {}
+                Some(output) => {
+                    return Err(Error::Interface(format!(
+                        "{}: {} at location {} doesn't match {} of {}!",
+                        self.name, input.name, input.location, output.name, previous.name,
+                    )))
+                }
+                None => {
+                    return Err(Error::Interface(format!(
+                        "{}: {} at location {} isn't written by {}!",
+                        self.name, input.name, input.location, previous.name,
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Layout entries of descriptor `set` used by any of `stages`, bindings listed in `dynamic`
+/// take a dynamic offset.
+pub fn layout_entries(stages: &[&Reflection], set: u32, dynamic: &[u32]) -> Result<Vec<wgpu::BindGroupLayoutEntry>> {
+    let mut entries: Vec<wgpu::BindGroupLayoutEntry> = Vec::new();
+    for reflection in stages {
+        for binding in reflection.bindings.iter().filter(|b| b.set == set) {
+            let mut ty = binding.ty.clone();
+            match &mut ty {
+                wgpu::BindingType::UniformBuffer { dynamic: d, .. } | wgpu::BindingType::StorageBuffer { dynamic: d, .. } => {
+                    *d = dynamic.contains(&binding.binding);
+                }
+                _ => {}
+            }
+            match entries.iter_mut().find(|e| e.binding == binding.binding) {
+                Some(entry) => {
+                    let merged = match (&mut entry.ty, &ty) {
+                        // stages may declare a smaller prefix of the same block
+                        (
+                            wgpu::BindingType::UniformBuffer { min_binding_size: a, .. },
+                            wgpu::BindingType::UniformBuffer { min_binding_size: b, .. },
+                        ) => {
+                            *a = (*a).max(*b);
+                            true
+                        }
+                        (a, b) => a == b,
+                    };
+                    if !merged || entry.count != binding.count {
+                        return Err(Error::Interface(format!(
+                            "{}: {} at set {} binding {} differs from the other stages!",
+                            reflection.name, binding.name, set, binding.binding,
+                        )));
+                    }
+                    entry.visibility |= reflection.stage;
+                }
+                None => entries.push(wgpu::BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility: reflection.stage,
+                    ty,
+                    count: binding.count,
+                }),
+            }
+        }
+    }
+    entries.sort_by_key(|e| e.binding);
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal SPIR-V assembler, ids are picked by the caller.
+    struct Assembler {
+        words: Vec<u32>,
+    }
+
+    impl Assembler {
+        fn new() -> Self {
+            Self { words: vec![MAGIC, 0x0001_0000, 0, 100, 0] }
+        }
+
+        fn op(&mut self, opcode: u32, operands: &[u32]) -> &mut Self {
+            self.words.push(((operands.len() as u32 + 1) << 16) | opcode);
+            self.words.extend_from_slice(operands);
+            self
+        }
+
+        fn name(&mut self, id: u32, name: &str) -> &mut Self {
+            let mut bytes = name.as_bytes().to_vec();
+            bytes.resize((bytes.len() / 4 + 1) * 4, 0);
+            let mut operands = vec![id];
+            operands.extend(bytes.chunks(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])));
+            self.op(OP_NAME, &operands)
+        }
+    }
+
+    const FLOAT: u32 = 1;
+    const VEC2: u32 = 2;
+    const VEC3: u32 = 3;
+    const VEC4: u32 = 4;
+    const MAT4: u32 = 5;
+    const BLOCK_TYPE: u32 = 6;
+    const BLOCK_PTR: u32 = 7;
+    const IN_VEC3: u32 = 8;
+    const IN_VEC2: u32 = 9;
+    const POS: u32 = 10;
+    const NORM: u32 = 11;
+    const UV: u32 = 12;
+    const UBO: u32 = 13;
+
+    /// A vertex shader with position, normal and texcoord inputs and a camera block at binding 0.
+    fn vertex_shader(uv_location: u32) -> Vec<u32> {
+        let mut asm = Assembler::new();
+        asm.op(OP_ENTRY_POINT, &[0, 99, u32::from_le_bytes(*b"main"), 0, POS, NORM, UV])
+            .name(POS, "a_Pos")
+            .name(NORM, "a_Norm")
+            .name(UV, "a_Texcoord")
+            .name(UBO, "Locals")
+            .op(OP_DECORATE, &[POS, LOCATION, 0])
+            .op(OP_DECORATE, &[NORM, LOCATION, 1])
+            .op(OP_DECORATE, &[UV, LOCATION, uv_location])
+            .op(OP_DECORATE, &[BLOCK_TYPE, BLOCK])
+            .op(OP_MEMBER_DECORATE, &[BLOCK_TYPE, 0, OFFSET, 0])
+            .op(OP_MEMBER_DECORATE, &[BLOCK_TYPE, 0, MATRIX_STRIDE, 16])
+            .op(OP_MEMBER_DECORATE, &[BLOCK_TYPE, 1, OFFSET, 64])
+            .op(OP_MEMBER_DECORATE, &[BLOCK_TYPE, 1, MATRIX_STRIDE, 16])
+            .op(OP_DECORATE, &[UBO, DESCRIPTOR_SET, 0])
+            .op(OP_DECORATE, &[UBO, BINDING, 0])
+            .op(OP_TYPE_FLOAT, &[FLOAT, 32])
+            .op(OP_TYPE_VECTOR, &[VEC2, FLOAT, 2])
+            .op(OP_TYPE_VECTOR, &[VEC3, FLOAT, 3])
+            .op(OP_TYPE_VECTOR, &[VEC4, FLOAT, 4])
+            .op(OP_TYPE_MATRIX, &[MAT4, VEC4, 4])
+            .op(OP_TYPE_STRUCT, &[BLOCK_TYPE, MAT4, MAT4])
+            .op(OP_TYPE_POINTER, &[BLOCK_PTR, UNIFORM, BLOCK_TYPE])
+            .op(OP_TYPE_POINTER, &[IN_VEC3, INPUT, VEC3])
+            .op(OP_TYPE_POINTER, &[IN_VEC2, INPUT, VEC2])
+            .op(OP_VARIABLE, &[IN_VEC3, POS, INPUT])
+            .op(OP_VARIABLE, &[IN_VEC3, NORM, INPUT])
+            .op(OP_VARIABLE, &[IN_VEC2, UV, INPUT])
+            .op(OP_VARIABLE, &[BLOCK_PTR, UBO, UNIFORM])
+            .op(OP_FUNCTION, &[0, 99, 0, 0]);
+        asm.words.clone()
+    }
+
+    fn attributes() -> [wgpu::VertexAttributeDescriptor; 3] {
+        [
+            wgpu::VertexAttributeDescriptor { format: wgpu::VertexFormat::Float3, offset: 0, shader_location: 0 },
+            wgpu::VertexAttributeDescriptor { format: wgpu::VertexFormat::Float3, offset: 12, shader_location: 1 },
+            wgpu::VertexAttributeDescriptor { format: wgpu::VertexFormat::Float2, offset: 24, shader_location: 2 },
+        ]
+    }
+
+    #[test]
+    fn reflects_workgroup_size() {
+        let mut asm = Assembler::new();
+        asm.op(OP_ENTRY_POINT, &[5, 99, u32::from_le_bytes(*b"main"), 0])
+            .op(OP_EXECUTION_MODE, &[99, LOCAL_SIZE, 64, 1, 1])
+            .op(OP_FUNCTION, &[0, 99, 0, 0]);
+        let reflection = Reflection::new("test.comp", &asm.words).unwrap();
+        assert_eq!(reflection.stage, wgpu::ShaderStage::COMPUTE);
+        assert_eq!(reflection.workgroup_size, Some([64, 1, 1]));
+    }
+
+    #[test]
+    fn reflects_inputs_and_blocks() {
+        let reflection = Reflection::new("test.vert", &vertex_shader(2)).unwrap();
+        assert_eq!(reflection.stage, wgpu::ShaderStage::VERTEX);
+        let inputs: Vec<_> = reflection.inputs.iter().map(|v| (v.name.as_str(), v.location, v.format)).collect();
+        assert_eq!(inputs, vec![
+            ("a_Pos", 0, Some(wgpu::VertexFormat::Float3)),
+            ("a_Norm", 1, Some(wgpu::VertexFormat::Float3)),
+            ("a_Texcoord", 2, Some(wgpu::VertexFormat::Float2)),
+        ]);
+        assert_eq!(reflection.bindings.len(), 1);
+        assert_eq!(reflection.bindings[0].ty, wgpu::BindingType::UniformBuffer {
+            dynamic: false,
+            min_binding_size: wgpu::BufferSize::new(128),
+        });
+        reflection.check_vertex_inputs(&attributes()).unwrap();
+    }
+
+    #[test]
+    fn shared_location_is_an_error() {
+        let error = Reflection::new("test.vert", &vertex_shader(1)).unwrap_err();
+        assert!(error.to_string().contains("share location 1"), "{}", error);
+    }
+
+    #[test]
+    fn layout_entries_merge_stages() {
+        let vertex = Reflection::new("test.vert", &vertex_shader(2)).unwrap();
+        let fragment = Reflection { name: "test.frag".into(), stage: wgpu::ShaderStage::FRAGMENT, ..vertex.clone() };
+        let entries = layout_entries(&[&vertex, &fragment], 0, &[0]).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].visibility, wgpu::ShaderStage::VERTEX | wgpu::ShaderStage::FRAGMENT);
+        assert!(entries[0].ty.has_dynamic_offset());
+        assert!(layout_entries(&[&vertex], 1, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn mismatched_attribute_is_an_error() {
+        let reflection = Reflection::new("test.vert", &vertex_shader(2)).unwrap();
+        let mut attributes = attributes();
+        attributes[2].format = wgpu::VertexFormat::Float3;
+        assert!(reflection.check_vertex_inputs(&attributes).is_err());
+        assert!(reflection.check_vertex_inputs(&attributes[..2]).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use std::time::{Duration, Instant};
+
+/// Source of the frame times, swapped for a `ManualClock` to step deterministically.
+pub trait Clock {
+    /// Time since the clock started.
+    fn now(&self) -> Duration;
+}
+
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Only moves when advanced.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ManualClock {
+    now: Duration,
+}
+
+impl ManualClock {
+    pub fn advance(&mut self, dt: Duration) {
+        self.now += dt;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now
+    }
+}
+
+/// Timing of one frame, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+    /// Since the previous frame, 0 for the first one.
+    pub dt: f32,
+    /// Since the first frame.
+    pub elapsed: f64,
+    /// Counts from 0.
+    pub index: u64,
+    /// Fixed updates due before this frame.
+    pub fixed_steps: u32,
+    /// How far into the next fixed step this frame is, from 0 to 1, for interpolating
+    /// between fixed updates.
+    pub fixed_alpha: f32,
+}
+
+/// Measures frames against a `Clock` and accumulates fixed update steps.
+pub struct FrameTimer<C: Clock> {
+    clock: C,
+    fixed_step: Duration,
+    first: Option<Duration>,
+    last: Duration,
+    index: u64,
+    accumulated: Duration,
+}
+
+impl<C: Clock> FrameTimer<C> {
+    /// Fixed steps dropped after a stall, instead of catching up all at once.
+    pub const MAX_FIXED_STEPS: u32 = 8;
+
+    pub fn new(clock: C, fixed_step: Duration) -> Self {
+        Self {
+            clock,
+            fixed_step,
+            first: None,
+            last: Duration::default(),
+            index: 0,
+            accumulated: Duration::default(),
+        }
+    }
+
+    pub fn clock(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    pub fn fixed_step(&self) -> Duration {
+        self.fixed_step
+    }
+
+    /// Starts the next frame's `dt` now, so time spent idle between frames isn't simulated.
+    pub fn skip(&mut self) {
+        self.last = self.clock.now();
+    }
+
+    /// Starts the next frame.
+    pub fn tick(&mut self) -> FrameTime {
+        let now = self.clock.now();
+        let first = *self.first.get_or_insert(now);
+        let dt = if self.index == 0 { Duration::default() } else { now.saturating_sub(self.last) };
+        self.last = now;
+
+        self.accumulated += dt;
+        let mut fixed_steps = 0;
+        if self.fixed_step > Duration::default() {
+            while self.accumulated >= self.fixed_step {
+                self.accumulated -= self.fixed_step;
+                fixed_steps += 1;
+            }
+            if fixed_steps > Self::MAX_FIXED_STEPS {
+                fixed_steps = Self::MAX_FIXED_STEPS;
+            }
+        }
+
+        let time = FrameTime {
+            dt: dt.as_secs_f32(),
+            elapsed: now.saturating_sub(first).as_secs_f64(),
+            index: self.index,
+            fixed_steps,
+            fixed_alpha: if self.fixed_step > Duration::default() {
+                self.accumulated.as_secs_f32() / self.fixed_step.as_secs_f32()
+            } else {
+                0.0
+            },
+        };
+        self.index += 1;
+        time
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimelineEvent {
+    Play,
+    Pause,
+    /// Jumped to a time, by `seek` or by looping back to the start.
+    Seek(f32),
+    /// Stopped at the end of a timeline that doesn't loop.
+    Finish,
+}
+
+type Subscriber = Box<dyn FnMut(TimelineEvent)>;
+
+/// Playback position with play, pause, seek and looping. Subscribers are told about
+/// everything but the regular advancing, which they read from `time`.
+pub struct Timeline {
+    time: f32,
+    /// Seconds, `None` runs forever.
+    duration: Option<f32>,
+    pub speed: f32,
+    pub looping: bool,
+    playing: bool,
+    subscribers: Vec<(usize, Subscriber)>,
+    next_subscriber: usize,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl Timeline {
+    /// A playing timeline at 0.
+    pub fn new(duration: Option<f32>) -> Self {
+        Self {
+            time: 0.0,
+            duration,
+            speed: 1.0,
+            looping: true,
+            playing: true,
+            subscribers: Vec::new(),
+            next_subscriber: 0,
+        }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn duration(&self) -> Option<f32> {
+        self.duration
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Calls `callback` on every event until unsubscribed with the returned id.
+    pub fn subscribe(&mut self, callback: impl FnMut(TimelineEvent) + 'static) -> usize {
+        let id = self.next_subscriber;
+        self.next_subscriber += 1;
+        self.subscribers.push((id, Box::new(callback)));
+        id
+    }
+
+    pub fn unsubscribe(&mut self, id: usize) {
+        self.subscribers.retain(|(s, _)| *s != id);
+    }
+
+    fn emit(&mut self, event: TimelineEvent) {
+        for (_, callback) in &mut self.subscribers {
+            callback(event);
+        }
+    }
+
+    pub fn play(&mut self) {
+        if !self.playing {
+            self.playing = true;
+            // playing a finished timeline starts it over
+            if !self.looping && self.duration.map_or(false, |d| self.time >= d && self.speed > 0.0) {
+                self.seek(0.0);
+            }
+            self.emit(TimelineEvent::Play);
+        }
+    }
+
+    pub fn pause(&mut self) {
+        if self.playing {
+            self.playing = false;
+            self.emit(TimelineEvent::Pause);
+        }
+    }
+
+    /// Jumps to `time`, clamped to the duration.
+    pub fn seek(&mut self, time: f32) {
+        self.time = match self.duration {
+            Some(duration) => time.max(0.0).min(duration),
+            None => time.max(0.0),
+        };
+        self.emit(TimelineEvent::Seek(self.time));
+    }
+
+    pub fn set_duration(&mut self, duration: Option<f32>) {
+        self.duration = duration;
+        if let Some(duration) = duration {
+            self.time = self.time.min(duration);
+        }
+    }
+
+    /// Moves a playing timeline on by `dt` seconds of frame time.
+    pub fn advance(&mut self, dt: f32) {
+        if !self.playing {
+            return;
+        }
+        let time = self.time + dt * self.speed;
+        match self.duration {
+            Some(duration) if time >= duration || time < 0.0 => {
+                if self.looping && duration > 0.0 {
+                    self.time = time.rem_euclid(duration);
+                    self.emit(TimelineEvent::Seek(self.time));
+                } else {
+                    self.time = time.max(0.0).min(duration);
+                    self.playing = false;
+                    self.emit(TimelineEvent::Finish);
+                }
+            }
+            _ => self.time = time.max(0.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn frame_timer_follows_the_clock() {
+        let mut timer = FrameTimer::new(ManualClock::default(), Duration::from_millis(10));
+        let first = timer.tick();
+        assert_eq!((first.dt, first.index, first.fixed_steps), (0.0, 0, 0));
+
+        timer.clock().advance(Duration::from_millis(25));
+        let second = timer.tick();
+        assert_eq!((second.index, second.fixed_steps), (1, 2));
+        assert!((second.dt - 0.025).abs() < 1e-6);
+        assert!((second.fixed_alpha - 0.5).abs() < 1e-4);
+
+        timer.clock().advance(Duration::from_millis(5));
+        let third = timer.tick();
+        assert_eq!(third.fixed_steps, 1);
+        assert!((third.elapsed - 0.03).abs() < 1e-6);
+    }
+
+    #[test]
+    fn frame_timer_caps_fixed_steps() {
+        let mut timer = FrameTimer::new(ManualClock::default(), Duration::from_millis(10));
+        timer.tick();
+        timer.clock().advance(Duration::from_secs(1));
+        assert_eq!(timer.tick().fixed_steps, FrameTimer::<ManualClock>::MAX_FIXED_STEPS);
+        timer.clock().advance(Duration::from_millis(10));
+        assert_eq!(timer.tick().fixed_steps, 1);
+    }
+
+    #[test]
+    fn timeline_loops_and_finishes() {
+        let mut timeline = Timeline::new(Some(2.0));
+        timeline.advance(2.5);
+        assert!((timeline.time() - 0.5).abs() < 1e-6);
+
+        timeline.looping = false;
+        timeline.advance(2.0);
+        assert_eq!(timeline.time(), 2.0);
+        assert!(!timeline.is_playing());
+
+        timeline.play();
+        assert_eq!(timeline.time(), 0.0);
+    }
+
+    #[test]
+    fn timeline_notifies_subscribers() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut timeline = Timeline::new(Some(1.0));
+        let recorded = events.clone();
+        let id = timeline.subscribe(move |event| recorded.borrow_mut().push(event));
+
+        timeline.pause();
+        timeline.advance(0.5);
+        assert_eq!(timeline.time(), 0.0);
+        timeline.seek(0.75);
+        timeline.play();
+        timeline.unsubscribe(id);
+        timeline.pause();
+
+        assert_eq!(*events.borrow(), vec![TimelineEvent::Pause, TimelineEvent::Seek(0.75), TimelineEvent::Play]);
+    }
+}